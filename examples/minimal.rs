@@ -0,0 +1,73 @@
+// Draws a rectangle and some text into a window, which works without a
+// window manager. Press any key inside the window to quit.
+//
+//     cargo run --example minimal -- [DISPLAY]
+//
+// The display defaults to the DISPLAY environment variable.
+
+use std::env;
+use std::error;
+use xclient::{Connection, CreateGcBits, DecodedEvent, Rectangle};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn error::Error>> {
+    let display = env::args()
+        .nth(1)
+        .or_else(|| env::var("DISPLAY").ok())
+        .unwrap_or_else(|| ":0".to_string());
+    let mut connection = Connection::connect(&display).await?;
+
+    let screen = connection.default_screen();
+    let (root, black, white) = (screen.root(), screen.black_pixel, screen.white_pixel);
+
+    let rectangle = Rectangle {
+        x: 200,
+        y: 200,
+        width: 100,
+        height: 100,
+    };
+    let window_id = connection
+        .create_simple_window(&root, rectangle, 4, &[])
+        .await?;
+    connection.map_window(window_id).await?;
+
+    let font_id = connection.open_font().await?;
+    let gc_id = connection
+        .create_gc(
+            window_id,
+            &[
+                (CreateGcBits::Foreground, black),
+                (CreateGcBits::Background, white),
+                (CreateGcBits::Font, font_id),
+            ],
+        )
+        .await?;
+
+    while let Some(event) = connection.next_event().await {
+        match event {
+            DecodedEvent::Expose(_) => {
+                let rectangle = Rectangle {
+                    x: 10,
+                    y: 10,
+                    width: 30,
+                    height: 30,
+                };
+                connection
+                    .poly_fill_rectangle(window_id, gc_id, &[rectangle])
+                    .await?;
+                connection
+                    .image_text_8(window_id, gc_id, 10, 60, b"Hello")
+                    .await?;
+            }
+            DecodedEvent::KeyPress(_) => break,
+            _ => {}
+        }
+    }
+
+    connection.free_gc(gc_id).await?;
+    connection.close_font(font_id).await?;
+    connection.destroy_window(window_id).await?;
+    connection.disconnect().await?;
+
+    Ok(())
+}
@@ -0,0 +1,3515 @@
+#![warn(rust_2018_idioms)]
+#![warn(clippy::pedantic)]
+#![allow(unused_variables)]
+#![allow(dead_code)]
+#![allow(clippy::missing_errors_doc)]
+#![allow(clippy::missing_panics_doc)]
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use enumflags2::{make_bitflags, BitFlags};
+use futures_core::Stream;
+use num_traits::FromPrimitive;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryInto;
+use std::env;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::iter::Iterator;
+use std::mem;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{self, Mutex, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use std::vec::Vec;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+// the wire format of requests, replies and events
+mod proto;
+
+pub use proto::*;
+
+// Reassembles the chunks of an incremental (INCR) property transfer.
+// Once the requestor deleted the INCR property, the owner stores one
+// chunk after the other in the property, each time waiting for the
+// requestor to delete it. A chunk of length 0 ends the transfer.
+struct IncrTransfer {
+    data: Vec<u8>,
+    complete: bool,
+}
+
+// The size given in the INCR property is announced by the selection
+// owner, only this much of it is allocated up front.
+const INCR_PREALLOCATION_LIMIT: usize = 1 << 20;
+
+impl IncrTransfer {
+    // `size_hint` is the lower bound of the size given in the INCR
+    // property
+    fn new(size_hint: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(size_hint.min(INCR_PREALLOCATION_LIMIT)),
+            complete: false,
+        }
+    }
+
+    fn push_chunk(&mut self, reply: &GetPropertyReply) {
+        if reply.format == 0 {
+            // the property doesn't exist (anymore), this isn't a chunk
+            // but a stale notification
+            return;
+        }
+
+        if reply.value.is_empty() {
+            self.complete = true;
+        } else {
+            self.data.extend_from_slice(&reply.value);
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+struct IdGenerator {
+    last: u32,
+    max: u32,
+    base: u32,
+    inc: u32,
+    // the first id of the current range, `inc` for the ids of the
+    // resource id mask
+    first: u32,
+    // ranges used up before the current one, as first and last id
+    used_up: Vec<(u32, u32)>,
+    // ids of destroyed or freed resources, which are handed out again
+    free: Vec<u32>,
+}
+
+impl IdGenerator {
+    fn new(base: u32, mask: u32) -> Self {
+        Self {
+            last: 0,
+            max: mask,
+            base,
+            inc: mask & (!mask + 1),
+            first: mask & (!mask + 1),
+            used_up: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    // The resource `id` is gone, so its id can be reused. The server
+    // handles requests in order, so a request creating a resource with
+    // the id again comes after the one which freed it. Ids this
+    // generator didn't hand out, like the ids of windows of other
+    // clients, and ids freed twice are ignored, otherwise the server
+    // would answer the creation with an IDChoice error.
+    fn free(&mut self, id: u32) {
+        if self.handed_out(id) && !self.free.contains(&id) {
+            self.free.push(id);
+        }
+    }
+
+    fn handed_out(&self, id: u32) -> bool {
+        let in_range = |first: u32, last: u32| {
+            (first..=last).contains(&id) && (id - first).is_multiple_of(self.inc)
+        };
+
+        (self.last >= self.first && in_range(self.first | self.base, self.last | self.base))
+            || self
+                .used_up
+                .iter()
+                .any(|&(first, last)| in_range(first, last))
+    }
+
+    fn is_exhausted(&self) -> bool {
+        self.free.is_empty() && self.max - self.last < self.inc
+    }
+
+    // Continues with the ids of GetXIDRange, like libxcb. The ids
+    // returned by the server contain the resource id base already.
+    // Returns whether the range has any ids, the server answers with a
+    // count of 0 if there are none left.
+    fn set_range(&mut self, start_id: u32, count: u32) -> bool {
+        if count == 0 || start_id < self.inc {
+            return false;
+        }
+        if self.last >= self.first {
+            self.used_up
+                .push((self.first | self.base, self.last | self.base));
+        }
+        self.first = start_id;
+        self.last = start_id - self.inc;
+        self.max = start_id.saturating_add((count - 1).saturating_mul(self.inc));
+
+        true
+    }
+}
+
+impl Iterator for IdGenerator {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(id) = self.free.pop() {
+            return Some(id);
+        }
+
+        // Hands out inc, 2 * inc, ... up to and including the mask, the
+        // same ids as libxcb. The last id is handed out when `last`
+        // reaches `max`, so the next step would go past the mask.
+        if self.max - self.last < self.inc {
+            return None;
+        }
+
+        self.last += self.inc;
+
+        Some(self.last | self.base)
+    }
+}
+
+// authorization sent in the connection setup
+#[derive(Clone, Debug)]
+pub struct Authorization {
+    // protocol name, e.g. MIT-MAGIC-COOKIE-1
+    pub name: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+// address families of Xauthority entries
+const FAMILY_INTERNET: u16 = 0;
+const FAMILY_INTERNET6: u16 = 6;
+const FAMILY_LOCAL: u16 = 256;
+const FAMILY_WILD: u16 = 65535;
+
+impl Authorization {
+    // Finds the MIT-MAGIC-COOKIE-1 of display `number` of `host`, `None`
+    // for this machine, in the file named by XAUTHORITY, otherwise
+    // ~/.Xauthority. Returns `None` if there is no such file or entry.
+    #[must_use]
+    pub fn from_xauthority(host: Option<&str>, number: u16) -> Option<Self> {
+        let path = env::var_os("XAUTHORITY")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".Xauthority")))?;
+        let xauthority = fs::read(path).ok()?;
+
+        Self::find(&xauthority, host, number)
+    }
+
+    // Every entry of an Xauthority file is its address family followed
+    // by the address, the display number, the protocol name and data,
+    // each prefixed by its 16 bit length. All numbers are big endian.
+    fn find(mut buf: &[u8], host: Option<&str>, number: u16) -> Option<Self> {
+        fn counted<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+            if buf.remaining() < 2 {
+                return None;
+            }
+            let len = usize::from(buf.get_u16());
+            if buf.remaining() < len {
+                return None;
+            }
+            let (field, rest) = buf.split_at(len);
+            *buf = rest;
+            Some(field)
+        }
+
+        let hostname = local_hostname();
+        let is_local =
+            host.is_none_or(|host| host == "localhost" || Some(host) == hostname.as_deref());
+        let number = number.to_string();
+        while buf.remaining() >= 2 {
+            let family = buf.get_u16();
+            let address = counted(&mut buf)?;
+            let entry_number = counted(&mut buf)?;
+            let name = counted(&mut buf)?;
+            let data = counted(&mut buf)?;
+
+            let address_matches = match (family, host) {
+                (FAMILY_WILD, _) => true,
+                // the address is the hostname, accept any if it's unknown
+                (FAMILY_LOCAL, _) if is_local => hostname
+                    .as_deref()
+                    .is_none_or(|hostname| hostname.as_bytes() == address),
+                (FAMILY_INTERNET, Some(host)) => host
+                    .parse::<Ipv4Addr>()
+                    .is_ok_and(|ip| ip.octets() == address),
+                (FAMILY_INTERNET6, Some(host)) => host
+                    .parse::<Ipv6Addr>()
+                    .is_ok_and(|ip| ip.octets() == address),
+                _ => false,
+            };
+            // an empty display number matches any display
+            let number_matches = entry_number.is_empty() || entry_number == number.as_bytes();
+            if address_matches && number_matches && name == b"MIT-MAGIC-COOKIE-1" {
+                return Some(Self {
+                    name: name.to_vec(),
+                    data: data.to_vec(),
+                });
+            }
+        }
+
+        None
+    }
+}
+
+fn local_hostname() -> Option<String> {
+    ["/proc/sys/kernel/hostname", "/etc/hostname"]
+        .into_iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .map(|hostname| hostname.trim().to_string())
+        .filter(|hostname| !hostname.is_empty())
+}
+
+async fn read_setup(
+    stream: &mut (impl AsyncRead + AsyncWrite + Unpin),
+    authorization: Option<&Authorization>,
+    byte_order: ByteOrder,
+) -> Result<Setup, ConnectionError> {
+    let (name, data) = authorization.map_or((&[][..], &[][..]), |authorization| {
+        (&authorization.name[..], &authorization.data[..])
+    });
+    let name_len = u16::try_from(name.len()).map_err(LengthOverflow::from)?;
+    let data_len = u16::try_from(data.len()).map_err(LengthOverflow::from)?;
+    let mut connection_req = Ordered::new(BytesMut::with_capacity(12), byte_order);
+    connection_req.put_u8(byte_order.setup_byte()); // byte order
+    connection_req.put_u8(0); // unused
+    connection_req.put_card16(11); // protocol major version
+    connection_req.put_card16(0); // protocol minor version
+    connection_req.put_card16(name_len); // length of authorization-protocol-name
+    connection_req.put_card16(data_len); // length of authorization-protocol-data
+    connection_req.put_card16(0);
+    connection_req.put_slice(name); // authorization-protocol-name
+    connection_req.put_bytes(0, pad(name.len()));
+    connection_req.put_slice(data); // authorization-protocol-data
+    connection_req.put_bytes(0, pad(data.len()));
+    stream.write_all_buf(&mut connection_req).await?;
+
+    // the first 8 bytes are common to all setup replies, they announce
+    // the length of the additional data
+    let mut header = [0; 8];
+    stream.read_exact(&mut header).await?;
+    let mut header = Ordered::new(&header[..], byte_order);
+    let status_code = header.get_u8();
+    if status_code > 2 {
+        return Err(ConnectionError::UnknownStatus(status_code));
+    }
+    // length of the reason if the connection is refused, unused
+    // otherwise
+    let reason_len = usize::from(header.get_u8());
+
+    let protocol_major_version = header.get_card16();
+    let protocol_minor_version = header.get_card16();
+
+    log::debug!("version major: {protocol_major_version}, minor: {protocol_minor_version}");
+
+    let additional_data_len = usize::from(header.get_card16()) * 4;
+    log::debug!("additional data len: {additional_data_len} [bytes]");
+
+    // read the complete additional data before decoding it, it may
+    // arrive in several fragments
+    let mut response = vec![0; additional_data_len];
+    stream.read_exact(&mut response).await?;
+    match status_code {
+        0 => {
+            let reason = response.get(..reason_len).unwrap_or(&response);
+            return Err(ConnectionError::SetupFailed {
+                reason: String::from_utf8_lossy(reason).into_owned(),
+            });
+        }
+        2 => {
+            // the reason takes all the additional data, padded
+            let reason = String::from_utf8_lossy(&response);
+            return Err(ConnectionError::AuthRequired {
+                reason: reason.trim_end_matches('\0').to_string(),
+            });
+        }
+        _ => {
+            log::info!("connection setup: success");
+        }
+    }
+    let mut response = Ordered::new(Bytes::from(response), byte_order);
+
+    let setup = Setup::from_bytes(&mut response)?;
+
+    log::debug!(
+        "remaining from response: {} {}",
+        response.remaining(),
+        additional_data_len
+    );
+
+    Ok(setup)
+}
+
+// replies are matched to their requests by the sequence number,
+// every request which expects a reply registers a sender here before
+// it is written to the stream. Requests of extensions are counted
+// like core requests and their replies look the same, so they are
+// matched the same way.
+type PendingReplies = sync::Arc<Mutex<HashMap<u16, ReplySender>>>;
+
+enum ReplySender {
+    // the request is answered by exactly one reply
+    Single(oneshot::Sender<Result<Bytes, XError>>),
+    // the request is answered by a series of replies, like
+    // ListFontsWithInfo, the receiver removes the entry after the last
+    Series(mpsc::UnboundedSender<Result<Bytes, XError>>),
+}
+
+impl ReplySender {
+    fn send_error(self, x_error: XError) {
+        match self {
+            Self::Single(one_tx) => {
+                let _ = one_tx.send(Err(x_error));
+            }
+            Self::Series(series_tx) => {
+                let _ = series_tx.send(Err(x_error));
+            }
+        }
+    }
+}
+
+// Hands the error to whoever waits for the reply of the request which
+// caused it. Errors of requests without a reply, which aren't checked,
+// are only logged.
+fn dispatch_error(x_error: XError, pending_replies: &PendingReplies) {
+    let reply_tx = pending_replies
+        .lock()
+        .unwrap()
+        .remove(&x_error.sequence_number);
+    if let Some(reply_tx) = reply_tx {
+        reply_tx.send_error(x_error);
+    } else {
+        log::debug!("{x_error}");
+    }
+}
+
+// decoders by the first event of their extension
+type ExtensionEventDecoders = sync::Arc<Mutex<Vec<(u8, Box<dyn ExtensionEventDecoder>)>>>;
+
+// `None` if no extension registered a decoder for the event or it
+// couldn't decode it
+fn decode_extension_event(
+    first_byte: u8,
+    buf: &mut impl OrderedBuf,
+    extension_event_decoders: &ExtensionEventDecoders,
+) -> Option<Box<dyn ExtensionEvent>> {
+    let extension_event_decoders = extension_event_decoders.lock().unwrap();
+    let (first_event, decoder) =
+        extension_event_decoders
+            .iter()
+            .find(|(first_event, decoder)| {
+                (*first_event..first_event.saturating_add(decoder.number_of_events()))
+                    .contains(&first_byte)
+            })?;
+    let byte_order = buf.byte_order();
+    let mut event = Ordered::new(buf.take(31), byte_order);
+    let extension_event = decoder.decode_event(first_byte - first_event, &mut event);
+    // skip whatever the decoder didn't read
+    let remaining = event.remaining();
+    event.advance(remaining);
+
+    extension_event
+}
+
+// Destination of `Connection::enable_protocol_dump`, shared with the
+// reader. It's only set once dumping is enabled, so otherwise checking
+// it is all that is done.
+type ProtocolDump = sync::Arc<OnceLock<Mutex<Box<dyn io::Write + Send>>>>;
+
+fn hex_dump(out: &mut dyn io::Write, bytes: &[u8]) -> io::Result<()> {
+    for (i, line) in bytes.chunks(16).enumerate() {
+        write!(out, "  {:04x}:", i * 16)?;
+        for byte in line {
+            write!(out, " {byte:02x}")?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+// the length field of the request starting `requests` in units of 4
+// bytes, the 32 bit one of a BIG-REQUESTS request
+fn request_len(byte_order: ByteOrder, requests: &[u8]) -> u32 {
+    let len = byte_order.card16([requests[2], requests[3]]);
+    if len == 0 && requests.len() >= 8 {
+        return byte_order.card32(requests[4..8].try_into().unwrap());
+    }
+
+    len.into()
+}
+
+// the name of the request starting `requests`
+fn request_name(requests: &[u8]) -> String {
+    let opcode = requests[0];
+    Opcodes::from_u8(opcode).map_or_else(
+        || format!("extension {opcode} minor {}", requests[1]),
+        |opcode| format!("{opcode:?}"),
+    )
+}
+
+// Dumps the requests in `requests`, the first of them has
+// `sequence_number`. Errors of the dump are ignored, it's a debugging
+// aid only.
+fn dump_requests(
+    protocol_dump: &ProtocolDump,
+    byte_order: ByteOrder,
+    mut sequence_number: u16,
+    mut requests: &[u8],
+) {
+    let Some(out) = protocol_dump.get() else {
+        return;
+    };
+    let mut out = out.lock().unwrap();
+    while requests.len() >= 4 {
+        let len = (request_len(byte_order, requests) as usize * 4).clamp(4, requests.len());
+        let name = request_name(requests);
+        let (request, rest) = requests.split_at(len);
+        let _ = writeln!(out, "> request {sequence_number} {name}, {len} bytes")
+            .and_then(|()| hex_dump(&mut **out, request));
+        sequence_number = sequence_number.wrapping_add(1);
+        requests = rest;
+    }
+}
+
+// dumps an error, reply or event as framed by `read_responses`
+fn dump_response(protocol_dump: &ProtocolDump, byte_order: ByteOrder, response: &[u8]) {
+    let Some(out) = protocol_dump.get() else {
+        return;
+    };
+    let mut out = out.lock().unwrap();
+    let sequence_number = byte_order.card16([response[2], response[3]]);
+    let annotation = match response[0] {
+        0 => format!(
+            "error {:?} of request {sequence_number}",
+            ErrorCode::from_u8(response[1])
+        ),
+        1 => format!(
+            "reply to request {sequence_number}, {} bytes",
+            response.len()
+        ),
+        first_byte => {
+            let code = first_byte & 0x7f;
+            let name = Events::from_u8(code)
+                .map_or_else(|| format!("{code}"), |event| format!("{event:?}"));
+            let sent = if first_byte & 0x80 == 0 {
+                ""
+            } else {
+                " (SendEvent)"
+            };
+            format!("event {name}{sent} after request {sequence_number}")
+        }
+    };
+    let _ = writeln!(out, "< {annotation}").and_then(|()| hex_dump(&mut **out, response));
+}
+
+// Length of a reply in bytes. After the reply code, a byte specific
+// to the reply, the sequence number and the reply length, 24 more
+// bytes follow, which complete the 32 bytes every reply has, then
+// `reply_length` units of 4 bytes of additional data.
+const fn total_reply_len(reply_length: u32) -> usize {
+    32 + reply_length as usize * 4
+}
+
+// Reads responses until the connection fails or is closed by the
+// server. The pending replies are failed then, instead of leaving
+// their cookies waiting forever.
+async fn read_responses(
+    read_stream: impl AsyncRead + Unpin,
+    pending_replies: PendingReplies,
+    extension_event_decoders: ExtensionEventDecoders,
+    events: mpsc::UnboundedSender<DecodedEvent>,
+    protocol_dump: ProtocolDump,
+    byte_order: ByteOrder,
+) -> io::Result<()> {
+    let result = dispatch_responses(
+        read_stream,
+        &pending_replies,
+        &extension_event_decoders,
+        &events,
+        &protocol_dump,
+        byte_order,
+    )
+    .await;
+    // dropping the senders wakes up whoever waits for a reply
+    pending_replies.lock().unwrap().clear();
+
+    result
+}
+
+async fn dispatch_responses(
+    mut read_stream: impl AsyncRead + Unpin,
+    pending_replies: &PendingReplies,
+    extension_event_decoders: &ExtensionEventDecoders,
+    events: &mpsc::UnboundedSender<DecodedEvent>,
+    protocol_dump: &ProtocolDump,
+    byte_order: ByteOrder,
+) -> io::Result<()> {
+    let mut response_buf = BytesMut::new();
+    loop {
+        // Every reply contains a 32-bit length field expressed in units
+        // of four bytes. Every reply consists of 32 bytes followed by
+        // zero or more additional bytes of data, as specified in the
+        // length field. Unused bytes within a reply are not guaranteed to
+        // be zero. Every reply also contains the least significant 16
+        // bits of the sequence number of the corresponding request.
+        if read_stream.read_buf(&mut response_buf).await? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        while response_buf.remaining() >= 32 {
+            match response_buf[0] {
+                0 => {
+                    let error = response_buf.split_to(32);
+                    dump_response(protocol_dump, byte_order, &error);
+                    let x_error = XError::from_bytes(&mut Ordered::new(error, byte_order));
+                    dispatch_error(x_error, pending_replies);
+                }
+                1 => {
+                    let reply_length = byte_order.card32(response_buf[4..8].try_into().unwrap());
+                    let total_reply_len = total_reply_len(reply_length);
+                    if response_buf.remaining() < total_reply_len {
+                        break;
+                    }
+                    let reply = response_buf.split_to(total_reply_len).freeze();
+                    dump_response(protocol_dump, byte_order, &reply);
+                    let sequence_number = byte_order.card16([reply[2], reply[3]]);
+                    let mut pending_replies = pending_replies.lock().unwrap();
+                    match pending_replies.remove(&sequence_number) {
+                        Some(ReplySender::Single(one_tx)) => {
+                            let _ = one_tx.send(Ok(reply));
+                        }
+                        Some(ReplySender::Series(series_tx)) => {
+                            // keep the entry for the following replies,
+                            // unless nobody waits for them anymore
+                            if series_tx.send(Ok(reply)).is_ok() {
+                                pending_replies
+                                    .insert(sequence_number, ReplySender::Series(series_tx));
+                            }
+                        }
+                        None => log::debug!("unexpected reply: {reply:?}"),
+                    }
+                }
+                first_byte => {
+                    let event_bytes = response_buf.split_to(32).freeze();
+                    dump_response(protocol_dump, byte_order, &event_bytes);
+                    let mut event_buf = Ordered::new(event_bytes.slice(1..), byte_order);
+                    // the most significant bit is set for events
+                    // generated by SendEvent
+                    let code = first_byte & 0x7f;
+                    let decoded = if let Some(event) = Events::from_u8(code) {
+                        decode_event(event, &mut event_buf)
+                    } else {
+                        // events of extensions
+                        let decoded =
+                            decode_extension_event(code, &mut event_buf, extension_event_decoders);
+                        if decoded.is_none() {
+                            log::debug!("unknown event {code}");
+                        }
+                        decoded.map(DecodedEvent::Extension)
+                    };
+                    let decoded = decoded.unwrap_or(DecodedEvent::Other(event_bytes));
+                    // nobody might be interested in events anymore
+                    let _ = events.send(decoded);
+                }
+            }
+        }
+    }
+}
+
+pub struct Cookie {
+    sequence_number: u16,
+    byte_order: ByteOrder,
+    reply: oneshot::Receiver<Result<Bytes, XError>>,
+}
+
+// why a cookie has no reply
+#[derive(Debug)]
+pub enum ReplyError {
+    // the server answered the request with an error
+    X(XError),
+    // the connection is gone before the reply arrived
+    Disconnected,
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::X(x_error) => write!(f, "{x_error}"),
+            Self::Disconnected => write!(f, "connection to the server is gone"),
+        }
+    }
+}
+
+impl error::Error for ReplyError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::X(x_error) => Some(x_error),
+            Self::Disconnected => None,
+        }
+    }
+}
+
+impl Cookie {
+    // sequence number of the request, which is also reported by an
+    // error caused by it
+    #[must_use]
+    pub fn sequence(&self) -> u16 {
+        self.sequence_number
+    }
+
+    pub async fn reply(self) -> Result<Ordered<Bytes>, ReplyError> {
+        match self.reply.await {
+            Ok(Ok(reply)) => Ok(Ordered::new(reply, self.byte_order)),
+            Ok(Err(x_error)) => Err(ReplyError::X(x_error)),
+            Err(oneshot::error::RecvError { .. }) => Err(ReplyError::Disconnected),
+        }
+    }
+}
+
+// A display name like `:1`, `hostname:0.2` or `unix/:0`, the format of
+// DISPLAY: an optional protocol and host, the display number and an
+// optional screen number. A bare number like `1` is taken as `:1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Display {
+    // `None` for the Unix socket on this machine
+    pub host: Option<String>,
+    pub number: u16,
+    // index of the screen in `Setup::screens`, 0 if not given
+    pub screen: usize,
+}
+
+#[derive(Debug)]
+pub struct ParseDisplayError(String);
+
+impl fmt::Display for ParseDisplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid display name {:?}", self.0)
+    }
+}
+
+impl error::Error for ParseDisplayError {}
+
+impl FromStr for Display {
+    type Err = ParseDisplayError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let error = || ParseDisplayError(name.to_string());
+        let (host, number) = name.rsplit_once(':').unwrap_or(("", name));
+        let (protocol, host) = host.split_once('/').unwrap_or(("", host));
+        let (number, screen) = number.split_once('.').unwrap_or((number, "0"));
+
+        Ok(Self {
+            host: (protocol != "unix" && !host.is_empty() && host != "unix")
+                .then(|| host.to_string()),
+            number: number.parse().map_err(|_| error())?,
+            screen: screen.parse().map_err(|_| error())?,
+        })
+    }
+}
+
+pub struct Connection {
+    setup: Setup,
+    // index of the screen of the display name, see `default_screen`
+    screen: usize,
+    write_stream: Box<dyn AsyncWrite + Send + Unpin>,
+    request_buf: Ordered<BytesMut>,
+    id_generator: IdGenerator,
+    sequence_number: u16,
+    pending_replies: PendingReplies,
+    reader: JoinHandle<io::Result<()>>,
+    // result of QueryExtension by extension name, `None` if the
+    // extension isn't present
+    extensions: HashMap<String, Option<ExtensionInfo>>,
+    // extensions advertised by ListExtensions, once
+    // `available_extensions` asked for them
+    available_extensions: Option<Vec<String>>,
+    extension_event_decoders: ExtensionEventDecoders,
+    // events are forwarded by the reader as they are received
+    events: mpsc::UnboundedReceiver<DecodedEvent>,
+    // events received while waiting for a specific event
+    pending_events: VecDeque<DecodedEvent>,
+    // set if `with_server_grabbed` didn't finish, the UngrabServer is
+    // sent before the next request then
+    ungrab_server_pending: sync::Arc<AtomicBool>,
+    protocol_dump: ProtocolDump,
+    // asked for more ids once the id generator is exhausted, see
+    // `use_xc_misc`
+    xc_misc: Option<XcMiscExtension>,
+    // in units of 4 bytes, the one of the setup unless BIG-REQUESTS is
+    // enabled
+    maximum_request_length: u32,
+}
+
+impl Connection {
+    #[must_use]
+    pub fn setup(&self) -> &Setup {
+        &self.setup
+    }
+
+    // the screen given by the display name, the first one otherwise
+    #[must_use]
+    pub fn default_screen(&self) -> &Screen {
+        &self.setup.screens[self.screen]
+    }
+
+    // Sequence number of the request sent last, e.g. to correlate a
+    // logged error with the call which caused it. The number is
+    // assigned when the request is written.
+    #[must_use]
+    pub fn last_sequence_number(&self) -> u16 {
+        self.sequence_number
+    }
+
+    // Connects to the display named `display`, see `Display`, over TCP
+    // if it has a host, otherwise via the Unix socket. The
+    // MIT-MAGIC-COOKIE-1 of the display is sent if the Xauthority file
+    // has one.
+    pub async fn connect(display: &str) -> Result<Self, ConnectionError> {
+        let display: Display = display
+            .parse()
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+        let mut connection = if let Some(host) = &display.host {
+            Self::connect_tcp(host, display.number).await?
+        } else {
+            let stream = UnixStream::connect(format!("/tmp/.X11-unix/X{}", display.number)).await?;
+            let authorization = Authorization::from_xauthority(None, display.number);
+            Self::from_stream_with_authorization(stream, authorization.as_ref()).await?
+        };
+        if display.screen >= connection.setup.screens.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("display has no screen {}", display.screen),
+            )
+            .into());
+        }
+        connection.screen = display.screen;
+
+        Ok(connection)
+    }
+
+    // Connects to display number `display` of `host` over TCP, at port
+    // 6000 plus the display number, with its MIT-MAGIC-COOKIE-1 from
+    // the Xauthority file if there is one. Nagle's algorithm is disabled,
+    // otherwise a small request written right after another one, like
+    // the GetInputFocus of a checked request, waits for the
+    // acknowledgement of the first, which the server delays.
+    pub async fn connect_tcp(host: &str, display: u16) -> Result<Self, ConnectionError> {
+        let port = 6000u16.checked_add(display).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "display number too large")
+        })?;
+        let stream = TcpStream::connect((host, port)).await?;
+        stream.set_nodelay(true)?;
+        let authorization = Authorization::from_xauthority(Some(host), display);
+        Self::from_stream_with_authorization(stream, authorization.as_ref()).await
+    }
+
+    // sets up the connection over `stream` without authorization
+    pub async fn from_stream(
+        stream: impl AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    ) -> Result<Self, ConnectionError> {
+        Self::from_stream_with_authorization(stream, None).await
+    }
+
+    pub async fn from_stream_with_authorization(
+        stream: impl AsyncRead + AsyncWrite + Send + Unpin + 'static,
+        authorization: Option<&Authorization>,
+    ) -> Result<Self, ConnectionError> {
+        Self::from_stream_with_options(stream, authorization, ByteOrder::default()).await
+    }
+
+    // Sets up the connection over `stream`, whose requests and
+    // responses use `byte_order`. The server converts them if its own
+    // byte order differs, so LSB first, the default, is only a choice
+    // of convenience.
+    pub async fn from_stream_with_options(
+        mut stream: impl AsyncRead + AsyncWrite + Send + Unpin + 'static,
+        authorization: Option<&Authorization>,
+        byte_order: ByteOrder,
+    ) -> Result<Self, ConnectionError> {
+        let setup = read_setup(&mut stream, authorization, byte_order).await?;
+        let (read_stream, write_stream) = tokio::io::split(stream);
+        let pending_replies = PendingReplies::default();
+        let extension_event_decoders = ExtensionEventDecoders::default();
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let protocol_dump = ProtocolDump::default();
+        let reader = tokio::spawn(read_responses(
+            read_stream,
+            sync::Arc::clone(&pending_replies),
+            sync::Arc::clone(&extension_event_decoders),
+            events_tx,
+            sync::Arc::clone(&protocol_dump),
+            byte_order,
+        ));
+
+        Ok(Self {
+            id_generator: IdGenerator::new(setup.resource_id_base, setup.resource_id_mask),
+            maximum_request_length: setup.maximum_request_length.into(),
+            setup,
+            screen: 0,
+            write_stream: Box::new(write_stream),
+            request_buf: Ordered::new(BytesMut::new(), byte_order),
+            sequence_number: 0,
+            pending_replies,
+            reader,
+            extensions: HashMap::new(),
+            available_extensions: None,
+            extension_event_decoders,
+            events,
+            pending_events: VecDeque::new(),
+            ungrab_server_pending: sync::Arc::default(),
+            protocol_dump,
+            xc_misc: None,
+        })
+    }
+
+    // byte order of the requests and responses of the connection
+    #[must_use]
+    pub fn byte_order(&self) -> ByteOrder {
+        self.request_buf.byte_order()
+    }
+
+    // Once the ids of the resource id mask are used up, ask XC-MISC for
+    // further ids instead of failing to create resources.
+    pub fn use_xc_misc(&mut self, xc_misc: XcMiscExtension) {
+        self.xc_misc = Some(xc_misc);
+    }
+
+    // in units of 4 bytes, see `big_requests_enable`
+    #[must_use]
+    pub fn maximum_request_length(&self) -> u32 {
+        self.maximum_request_length
+    }
+
+    // Hex dumps every request sent and every reply, event and error
+    // received from now on to `writer`, each annotated with its name
+    // and sequence number, similar to xtrace. Enabling it again
+    // replaces the writer.
+    pub fn enable_protocol_dump(&mut self, writer: impl io::Write + Send + 'static) {
+        let writer: Box<dyn io::Write + Send> = Box::new(writer);
+        if let Some(out) = self.protocol_dump.get() {
+            *out.lock().unwrap() = writer;
+        } else {
+            let _ = self.protocol_dump.set(Mutex::new(writer));
+        }
+    }
+
+    // Writes the request in `request_buf`, which must not expect a
+    // reply. An error caused by the request is only logged, which
+    // suits best-effort requests like destroying a window which might
+    // be gone already.
+    async fn send_void_unchecked(&mut self) -> io::Result<u16> {
+        self.send_voids_unchecked(1).await
+    }
+
+    // Like `send_void_unchecked`, but `request_buf` holds `count`
+    // requests, which are written at once. Returns the sequence number
+    // of the last one.
+    async fn send_voids_unchecked(&mut self, count: u16) -> io::Result<u16> {
+        self.ensure_buffered_requests_fit()?;
+        self.send_pending_ungrab_server().await?;
+        self.sequence_number = self.sequence_number.wrapping_add(count);
+        dump_requests(
+            &self.protocol_dump,
+            self.byte_order(),
+            self.sequence_number.wrapping_sub(count).wrapping_add(1),
+            &self.request_buf,
+        );
+        self.write_stream
+            .write_all_buf(&mut self.request_buf)
+            .await?;
+
+        Ok(self.sequence_number)
+    }
+
+    // Writes the request in `request_buf`, which must not expect a
+    // reply, and fails if the server answers it with an error. Since
+    // there is no reply to wait for, a GetInputFocus request is sent
+    // afterwards. Replies and errors arrive in order, so once its reply
+    // arrived, any error for the request has been received as well.
+    async fn send_void_checked(&mut self) -> Result<u16, Box<dyn error::Error>> {
+        let cookie = self.send_request().await?;
+        get_input_focus(&mut self.request_buf);
+        self.send_request().await?.reply().await?;
+
+        let mut error = cookie.reply;
+        match error.try_recv() {
+            Ok(Err(x_error)) => return Err(x_error.into()),
+            Err(oneshot::error::TryRecvError::Closed) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "connection to the server is gone",
+                )
+                .into());
+            }
+            Ok(Ok(_)) | Err(oneshot::error::TryRecvError::Empty) => {}
+        }
+        self.pending_replies
+            .lock()
+            .unwrap()
+            .remove(&cookie.sequence_number);
+
+        Ok(cookie.sequence_number)
+    }
+
+    async fn send_pending_ungrab_server(&mut self) -> io::Result<()> {
+        if self.ungrab_server_pending.swap(false, Ordering::Relaxed) {
+            let mut buf = Ordered::new(BytesMut::new(), self.byte_order());
+            ungrab_server(&mut buf);
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            dump_requests(
+                &self.protocol_dump,
+                self.byte_order(),
+                self.sequence_number,
+                &buf,
+            );
+            self.write_stream.write_all_buf(&mut buf).await?;
+        }
+
+        Ok(())
+    }
+
+    // writes the request in `request_buf` and returns a cookie for its reply
+    async fn send_request(&mut self) -> io::Result<Cookie> {
+        let (one_tx, one_rx) = oneshot::channel();
+        self.write_request(ReplySender::Single(one_tx)).await?;
+
+        Ok(Cookie {
+            sequence_number: self.sequence_number,
+            byte_order: self.byte_order(),
+            reply: one_rx,
+        })
+    }
+
+    // writes the request in `request_buf`, which is answered by a series
+    // of replies, and returns its sequence number and the receiver of
+    // the replies
+    async fn send_request_with_replies(
+        &mut self,
+    ) -> io::Result<(u16, mpsc::UnboundedReceiver<Result<Bytes, XError>>)> {
+        let (series_tx, series_rx) = mpsc::unbounded_channel();
+        self.write_request(ReplySender::Series(series_tx)).await?;
+
+        Ok((self.sequence_number, series_rx))
+    }
+
+    async fn write_request(&mut self, reply_tx: ReplySender) -> io::Result<()> {
+        // nobody would answer the cookie anymore
+        if self.reader.is_finished() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "connection to the server is gone",
+            ));
+        }
+        self.ensure_buffered_requests_fit()?;
+        self.send_pending_ungrab_server().await?;
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        self.pending_replies
+            .lock()
+            .unwrap()
+            .insert(self.sequence_number, reply_tx);
+        dump_requests(
+            &self.protocol_dump,
+            self.byte_order(),
+            self.sequence_number,
+            &self.request_buf,
+        );
+        self.write_stream
+            .write_all_buf(&mut self.request_buf)
+            .await?;
+
+        Ok(())
+    }
+
+    // Creates a child window of `parent`, which is e.g. the root
+    // window of a screen or a frame window, at `rectangle` relative to
+    // the parent, excluding the border. Class, depth and visual are
+    // copied from the parent. The request is checked, so
+    // a `parent` which isn't a window, like a pixmap, yields an error
+    // instead of a window id which can't be used.
+    //
+    // `values` sets further window attributes, e.g. a backing store
+    // to preserve the window contents on servers which support it:
+    //
+    // (WindowAttributeBits::BackingStore, BackingStore::WhenMapped as u32)
+    pub async fn create_window(
+        &mut self,
+        parent: WindowId,
+        rectangle: Rectangle,
+        border_width: u16,
+        values: &[(WindowAttributeBits, u32)],
+    ) -> Result<WindowId, Box<dyn error::Error>> {
+        self.refill_ids().await?;
+        let window_id = create_window_request(
+            &mut self.request_buf,
+            &self.setup.screens[self.screen],
+            parent,
+            rectangle.x,
+            rectangle.y,
+            rectangle.width,
+            rectangle.height,
+            border_width,
+            0,
+            0,
+            0,
+            DEFAULT_EVENT_MASK,
+            values,
+            &mut self.id_generator,
+        )?;
+        self.send_void_checked().await?;
+
+        Ok(window_id)
+    }
+
+    // creates a top-level window with the depth and visual of `root`
+    pub async fn create_simple_window(
+        &mut self,
+        root: &RootInfo,
+        rectangle: Rectangle,
+        border_width: u16,
+        values: &[(WindowAttributeBits, u32)],
+    ) -> Result<WindowId, Box<dyn error::Error>> {
+        self.refill_ids().await?;
+        let window_id = create_window_request(
+            &mut self.request_buf,
+            &self.setup.screens[self.screen],
+            root.window,
+            rectangle.x,
+            rectangle.y,
+            rectangle.width,
+            rectangle.height,
+            border_width,
+            root.depth,
+            1,
+            root.visual,
+            DEFAULT_EVENT_MASK,
+            values,
+            &mut self.id_generator,
+        )?;
+        self.send_void_checked().await?;
+
+        Ok(window_id)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn grab_pointer(
+        &mut self,
+        owner_events: bool,
+        grab_window: WindowId,
+        event_mask: BitFlags<Event>,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+        confine_to: WindowId,
+        cursor: u32,
+        time: u32,
+    ) -> Result<GrabStatus, Box<dyn error::Error>> {
+        grab_pointer(
+            &mut self.request_buf,
+            owner_events,
+            grab_window,
+            event_mask,
+            pointer_mode,
+            keyboard_mode,
+            confine_to,
+            cursor,
+            time,
+        )?;
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GrabReply::from_bytes(&mut reply)?.status)
+    }
+
+    pub async fn grab_keyboard(
+        &mut self,
+        owner_events: bool,
+        grab_window: WindowId,
+        time: u32,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+    ) -> Result<GrabStatus, Box<dyn error::Error>> {
+        grab_keyboard(
+            &mut self.request_buf,
+            owner_events,
+            grab_window,
+            time,
+            pointer_mode,
+            keyboard_mode,
+        );
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GrabReply::from_bytes(&mut reply)?.status)
+    }
+
+    pub async fn ungrab_pointer(&mut self, time: u32) -> io::Result<()> {
+        ungrab_pointer(&mut self.request_buf, time);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn ungrab_keyboard(&mut self, time: u32) -> io::Result<()> {
+        ungrab_keyboard(&mut self.request_buf, time);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Repeats `grab` up to `attempts` times, waiting `delay` in
+    // between, as long as it fails because of a grab of another client.
+    // Returns the status of the last attempt.
+    pub async fn retry_grab(
+        &mut self,
+        attempts: u32,
+        delay: Duration,
+        mut grab: impl AsyncFnMut(&mut Self) -> Result<GrabStatus, Box<dyn error::Error>>,
+    ) -> Result<GrabStatus, Box<dyn error::Error>> {
+        let mut status = grab(self).await?;
+        for _attempt in 1..attempts {
+            if !status.is_transient() {
+                break;
+            }
+            sleep(delay).await;
+            status = grab(self).await?;
+        }
+
+        Ok(status)
+    }
+
+    // grabs the pointer, retrying while another client has grabbed it,
+    // see `retry_grab`
+    pub async fn grab_pointer_retry(
+        &mut self,
+        params: &GrabPointerParams,
+        attempts: u32,
+        delay: Duration,
+    ) -> Result<GrabStatus, Box<dyn error::Error>> {
+        self.retry_grab(attempts, delay, async |connection| {
+            connection
+                .grab_pointer(
+                    params.owner_events,
+                    params.grab_window,
+                    params.event_mask,
+                    params.pointer_mode,
+                    params.keyboard_mode,
+                    params.confine_to,
+                    params.cursor,
+                    params.time,
+                )
+                .await
+        })
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn grab_button(
+        &mut self,
+        owner_events: bool,
+        grab_window: WindowId,
+        event_mask: u16,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+        confine_to: WindowId,
+        cursor: u32,
+        button: u8,
+        modifiers: u16,
+    ) -> io::Result<()> {
+        grab_button(
+            &mut self.request_buf,
+            owner_events,
+            grab_window,
+            event_mask,
+            pointer_mode,
+            keyboard_mode,
+            confine_to,
+            cursor,
+            button,
+            modifiers,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn ungrab_button(
+        &mut self,
+        button: u8,
+        grab_window: WindowId,
+        modifiers: u16,
+    ) -> io::Result<()> {
+        ungrab_button(&mut self.request_buf, button, grab_window, modifiers);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn grab_key(
+        &mut self,
+        owner_events: bool,
+        grab_window: WindowId,
+        modifiers: u16,
+        key: u8,
+        pointer_mode: GrabMode,
+        keyboard_mode: GrabMode,
+    ) -> io::Result<()> {
+        grab_key(
+            &mut self.request_buf,
+            owner_events,
+            grab_window,
+            modifiers,
+            key,
+            pointer_mode,
+            keyboard_mode,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn ungrab_key(
+        &mut self,
+        key: u8,
+        grab_window: WindowId,
+        modifiers: u16,
+    ) -> io::Result<()> {
+        ungrab_key(&mut self.request_buf, key, grab_window, modifiers);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn allow_events(&mut self, mode: AllowEventsMode, time: u32) -> io::Result<()> {
+        allow_events(&mut self.request_buf, mode, time);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn set_input_focus(
+        &mut self,
+        revert_to: RevertTo,
+        focus: WindowId,
+        time: u32,
+    ) -> io::Result<()> {
+        set_input_focus(&mut self.request_buf, revert_to, focus, time);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn grab_server(&mut self) -> io::Result<()> {
+        grab_server(&mut self.request_buf);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn ungrab_server(&mut self) -> io::Result<()> {
+        ungrab_server(&mut self.request_buf);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Runs `f` while the server is grabbed, i.e. other clients are
+    // stalled, which makes a batch of requests like setting up a frame
+    // window atomic. The server is ungrabbed whatever `f` returns. If
+    // `f` panics or the returned future is dropped before it
+    // completes, the UngrabServer is sent along with the next request
+    // and at the latest the server releases the grab when the
+    // connection is closed.
+    pub async fn with_server_grabbed<R>(
+        &mut self,
+        f: impl AsyncFnOnce(&mut Self) -> R,
+    ) -> io::Result<R> {
+        struct Guard(sync::Arc<AtomicBool>);
+
+        impl Drop for Guard {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+
+        self.grab_server().await?;
+        let guard = Guard(sync::Arc::clone(&self.ungrab_server_pending));
+        let result = f(self).await;
+        // the guard queues the UngrabServer, which is sent right away
+        drop(guard);
+        self.send_pending_ungrab_server().await?;
+
+        Ok(result)
+    }
+
+    pub async fn configure_window(
+        &mut self,
+        window_id: WindowId,
+        commands: &[ConfigureWindowCommands],
+    ) -> io::Result<()> {
+        configure_window(&mut self.request_buf, window_id, commands)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Configures all windows, e.g. a frame and its client, without
+    // other clients observing the configuration in between, so no
+    // intermediate state is painted. All requests are written at once
+    // between a GrabServer and an UngrabServer. Thereby the server
+    // processes them without interruption, and as the UngrabServer is
+    // part of the same write, the grab can't outlive the batch.
+    pub async fn configure_windows_atomically(
+        &mut self,
+        windows: &[(WindowId, &[ConfigureWindowCommands])],
+    ) -> io::Result<()> {
+        let count = u16::try_from(windows.len() + 2).map_err(LengthOverflow::from)?;
+        // encoded separately, so nothing is left in `request_buf` if a
+        // request can't be encoded
+        let mut batch = Ordered::new(BytesMut::new(), self.byte_order());
+        grab_server(&mut batch);
+        for (window_id, commands) in windows {
+            configure_window(&mut batch, *window_id, commands)?;
+        }
+        ungrab_server(&mut batch);
+        self.request_buf.unsplit(batch.into_inner());
+        self.send_voids_unchecked(count).await?;
+
+        Ok(())
+    }
+
+    // Creates a top-level window for a menu, tooltip or splash screen,
+    // which the window manager doesn't redirect, i.e. it's neither
+    // framed nor placed, but mapped and configured as requested. The
+    // server saves what's below the window for the short time it's
+    // mapped, if it supports save-under.
+    pub async fn create_popup_window(
+        &mut self,
+        root: &RootInfo,
+        rectangle: Rectangle,
+        border_width: u16,
+        values: &[(WindowAttributeBits, u32)],
+    ) -> Result<WindowId, Box<dyn error::Error>> {
+        let values: Vec<_> = [
+            (WindowAttributeBits::OverrideRedirect, 1),
+            (WindowAttributeBits::SaveUnder, 1),
+        ]
+        .iter()
+        .chain(values)
+        .copied()
+        .collect();
+
+        self.create_simple_window(root, rectangle, border_width, &values)
+            .await
+    }
+
+    pub async fn map_window(&mut self, window_id: WindowId) -> io::Result<()> {
+        map_window_request(&mut self.request_buf, window_id);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn unmap_window(&mut self, window_id: WindowId) -> io::Result<()> {
+        unmap_window_request(&mut self.request_buf, window_id);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn destroy_window(&mut self, window_id: WindowId) -> io::Result<()> {
+        destroy_window_request(&mut self.request_buf, window_id);
+        self.send_void_unchecked().await?;
+        self.id_generator.free(window_id);
+
+        Ok(())
+    }
+
+    pub async fn reparent_window(
+        &mut self,
+        window_id: WindowId,
+        parent: WindowId,
+        x: i16,
+        y: i16,
+    ) -> io::Result<()> {
+        reparent_window(&mut self.request_buf, window_id, parent, x, y);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_window_attributes(
+        &mut self,
+        window_id: WindowId,
+    ) -> Result<WindowAttributesReply, Box<dyn error::Error>> {
+        get_window_attributes_request(&mut self.request_buf, window_id);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(WindowAttributesReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn get_geometry(
+        &mut self,
+        drawable: u32,
+    ) -> Result<GetGeometryReply, Box<dyn error::Error>> {
+        get_geometry(&mut self.request_buf, drawable);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetGeometryReply::from_bytes(&mut reply))
+    }
+
+    pub async fn query_tree(
+        &mut self,
+        window_id: WindowId,
+    ) -> Result<QueryTreeReply, Box<dyn error::Error>> {
+        query_tree(&mut self.request_buf, window_id);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(QueryTreeReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn translate_coordinates(
+        &mut self,
+        src_window: WindowId,
+        dst_window: WindowId,
+        src_x: i16,
+        src_y: i16,
+    ) -> Result<TranslateCoordinatesReply, Box<dyn error::Error>> {
+        translate_coordinates(&mut self.request_buf, src_window, dst_window, src_x, src_y);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(TranslateCoordinatesReply::from_bytes(&mut reply))
+    }
+
+    // Position of the origin of the window, i.e. the upper left corner
+    // inside its border, relative to its root window. Unlike the
+    // position of GetGeometry, which is relative to the parent, this
+    // includes the offsets of all ancestors, e.g. of the frame of a
+    // reparenting window manager.
+    pub async fn absolute_position(
+        &mut self,
+        window_id: WindowId,
+    ) -> Result<(i16, i16), Box<dyn error::Error>> {
+        // the root of the window's own screen, which might not be the
+        // first one
+        let root = self.get_geometry(window_id).await?.root;
+        let reply = self.translate_coordinates(window_id, root, 0, 0).await?;
+        if !reply.same_screen {
+            return Err(format!("window {window_id} isn't on the screen of its root").into());
+        }
+
+        Ok((reply.dst_x, reply.dst_y))
+    }
+
+    pub async fn query_pointer(
+        &mut self,
+        window_id: WindowId,
+    ) -> Result<QueryPointerReply, Box<dyn error::Error>> {
+        query_pointer(&mut self.request_buf, window_id);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(QueryPointerReply::from_bytes(&mut reply))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn warp_pointer(
+        &mut self,
+        src_window: WindowId,
+        dst_window: WindowId,
+        src_x: i16,
+        src_y: i16,
+        src_width: u16,
+        src_height: u16,
+        dst_x: i16,
+        dst_y: i16,
+    ) -> io::Result<()> {
+        warp_pointer(
+            &mut self.request_buf,
+            src_window,
+            dst_window,
+            src_x,
+            src_y,
+            src_width,
+            src_height,
+            dst_x,
+            dst_y,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Keeps the pointer in the center of `window_id` and reports its
+    // motion relative to the previous position, see `RelativePointer`.
+    // The window must select PointerMotion events.
+    pub async fn pointer_relative_mode(
+        &mut self,
+        window_id: WindowId,
+    ) -> Result<RelativePointer, Box<dyn error::Error>> {
+        let origin = self.query_pointer(window_id).await?;
+        let geometry = self.get_geometry(window_id).await?;
+        let center = (
+            i16::try_from(geometry.width / 2)?,
+            i16::try_from(geometry.height / 2)?,
+        );
+        self.warp_pointer(0, window_id, 0, 0, 0, 0, center.0, center.1)
+            .await?;
+
+        Ok(RelativePointer {
+            window_id,
+            center,
+            last: center,
+            warp_pending: true,
+            origin: (origin.root, origin.root_x, origin.root_y),
+        })
+    }
+
+    pub async fn shape_query_version(
+        &mut self,
+        shape: &ShapeExtension,
+    ) -> Result<ShapeQueryVersionReply, Box<dyn error::Error>> {
+        shape.query_version(&mut self.request_buf);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(ShapeQueryVersionReply::from_bytes(&mut reply))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn shape_combine(
+        &mut self,
+        shape: &ShapeExtension,
+        operation: ShapeOperations,
+        destination_kind: ShapeKind,
+        source_kind: ShapeKind,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+        source_window: WindowId,
+    ) -> io::Result<()> {
+        shape.combine(
+            &mut self.request_buf,
+            operation,
+            destination_kind,
+            source_kind,
+            window_id,
+            x_offset,
+            y_offset,
+            source_window,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn shape_rectangles(
+        &mut self,
+        shape: &ShapeExtension,
+        operation: ShapeOperations,
+        destination_kind: ShapeKind,
+        ordering: ShapeOrdering,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+        rectangles: &[Rectangle],
+    ) -> io::Result<()> {
+        shape.rectangles(
+            &mut self.request_buf,
+            operation,
+            destination_kind,
+            ordering,
+            window_id,
+            x_offset,
+            y_offset,
+            rectangles,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // `source_bitmap` is a pixmap of depth 1, e.g. of
+    // `create_pixmap_from_bitmap`
+    #[allow(clippy::too_many_arguments)]
+    pub async fn shape_mask(
+        &mut self,
+        shape: &ShapeExtension,
+        operation: ShapeOperations,
+        destination_kind: ShapeKind,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+        source_bitmap: Option<PixmapId>,
+    ) -> io::Result<()> {
+        shape.mask(
+            &mut self.request_buf,
+            operation,
+            destination_kind,
+            window_id,
+            x_offset,
+            y_offset,
+            source_bitmap,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn shape_offset(
+        &mut self,
+        shape: &ShapeExtension,
+        destination_kind: ShapeKind,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+    ) -> io::Result<()> {
+        shape.offset(
+            &mut self.request_buf,
+            destination_kind,
+            window_id,
+            x_offset,
+            y_offset,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn shape_query_extents(
+        &mut self,
+        shape: &ShapeExtension,
+        window_id: WindowId,
+    ) -> Result<ShapeQueryExtentsReply, Box<dyn error::Error>> {
+        shape.query_extents(&mut self.request_buf, window_id);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(ShapeQueryExtentsReply::from_bytes(&mut reply))
+    }
+
+    pub async fn shape_select_input(
+        &mut self,
+        shape: &ShapeExtension,
+        window_id: WindowId,
+        enable: bool,
+    ) -> io::Result<()> {
+        shape.select_input(&mut self.request_buf, window_id, enable);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn shape_input_selected(
+        &mut self,
+        shape: &ShapeExtension,
+        window_id: WindowId,
+    ) -> Result<ShapeInputSelectedReply, Box<dyn error::Error>> {
+        shape.input_selected(&mut self.request_buf, window_id);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(ShapeInputSelectedReply::from_bytes(&mut reply))
+    }
+
+    pub async fn shape_get_rectangles(
+        &mut self,
+        shape: &ShapeExtension,
+        window_id: WindowId,
+        kind: ShapeKind,
+    ) -> Result<ShapeGetRectanglesReply, Box<dyn error::Error>> {
+        shape.get_rectangles(&mut self.request_buf, window_id, kind);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(ShapeGetRectanglesReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn xv_query_extension(
+        &mut self,
+        xv: &XvExtension,
+    ) -> Result<XvQueryExtensionReply, Box<dyn error::Error>> {
+        xv.query_extension(&mut self.request_buf);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(XvQueryExtensionReply::from_bytes(&mut reply))
+    }
+
+    pub async fn xv_query_adaptors(
+        &mut self,
+        xv: &XvExtension,
+        window_id: WindowId,
+    ) -> Result<XvQueryAdaptorsReply, Box<dyn error::Error>> {
+        xv.query_adaptors(&mut self.request_buf, window_id);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(XvQueryAdaptorsReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn xv_query_encodings(
+        &mut self,
+        xv: &XvExtension,
+        port: XvPortId,
+    ) -> Result<XvQueryEncodingsReply, Box<dyn error::Error>> {
+        xv.query_encodings(&mut self.request_buf, port);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(XvQueryEncodingsReply::from_bytes(&mut reply)?)
+    }
+
+    // Enables BIG-REQUESTS, so requests may be as long as the maximum
+    // request length of the reply, which `maximum_request_length`
+    // reports from now on.
+    pub async fn big_requests_enable(
+        &mut self,
+        big_requests: &BigRequestsExtension,
+    ) -> Result<BigRequestsEnableReply, Box<dyn error::Error>> {
+        big_requests.enable(&mut self.request_buf);
+        let mut reply = self.send_request().await?.reply().await?;
+        let reply = BigRequestsEnableReply::from_bytes(&mut reply);
+        self.maximum_request_length = reply.maximum_request_length;
+
+        Ok(reply)
+    }
+
+    pub async fn xc_misc_get_xid_range(
+        &mut self,
+        xc_misc: &XcMiscExtension,
+    ) -> Result<GetXidRangeReply, Box<dyn error::Error>> {
+        xc_misc.get_xid_range(&mut self.request_buf);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetXidRangeReply::from_bytes(&mut reply))
+    }
+
+    // asks XC-MISC for further ids if the id generator is exhausted,
+    // fails if `use_xc_misc` wasn't called or the server has none left
+    async fn refill_ids(&mut self) -> io::Result<()> {
+        if !self.id_generator.is_exhausted() {
+            return Ok(());
+        }
+        let Some(xc_misc) = &self.xc_misc else {
+            return Err(IdsExhausted.into());
+        };
+        xc_misc.get_xid_range(&mut self.request_buf);
+        let mut reply = self
+            .send_request()
+            .await?
+            .reply()
+            .await
+            .map_err(io::Error::other)?;
+        let reply = GetXidRangeReply::from_bytes(&mut reply);
+        if !self.id_generator.set_range(reply.start_id, reply.count) {
+            return Err(IdsExhausted.into());
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_input_focus(&mut self) -> Result<GetInputFocusReply, Box<dyn error::Error>> {
+        get_input_focus(&mut self.request_buf);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetInputFocusReply::from_bytes(&mut reply)?)
+    }
+
+    // the keyboard mapping of `count` keycodes from `first_keycode` on,
+    // which has to be at least `Setup::min_keycode`
+    pub async fn get_keyboard_mapping(
+        &mut self,
+        first_keycode: u8,
+        count: u8,
+    ) -> Result<GetKeyboardMappingReply, Box<dyn error::Error>> {
+        get_keyboard_mapping(&mut self.request_buf, first_keycode, count);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetKeyboardMappingReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn change_keyboard_control(&mut self, values: &[KeyboardControl]) -> io::Result<()> {
+        change_keyboard_control(&mut self.request_buf, values)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn bell(&mut self, percent: i8) -> io::Result<()> {
+        bell(&mut self.request_buf, percent)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_screen_saver(&mut self) -> Result<GetScreenSaverReply, Box<dyn error::Error>> {
+        get_screen_saver(&mut self.request_buf);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetScreenSaverReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn list_fonts(&mut self) -> Result<ListFontsReply, Box<dyn error::Error>> {
+        list_fonts(&mut self.request_buf)?;
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(ListFontsReply::from_bytes(&mut reply)?)
+    }
+
+    // the fonts matching `pattern`, at most `max_names`; every font is
+    // answered by a reply of its own
+    pub async fn list_fonts_with_info(
+        &mut self,
+        max_names: u16,
+        pattern: &[u8],
+    ) -> Result<Vec<FontWithInfo>, Box<dyn error::Error>> {
+        list_fonts_with_info(&mut self.request_buf, max_names, pattern)?;
+        let (sequence_number, mut replies) = self.send_request_with_replies().await?;
+
+        let mut fonts = Vec::new();
+        loop {
+            match replies.recv().await {
+                Some(Ok(reply)) => {
+                    let mut reply = Ordered::new(reply, self.byte_order());
+                    if let Some(font) = FontWithInfo::from_bytes(&mut reply)? {
+                        fonts.push(font);
+                    } else {
+                        break;
+                    }
+                }
+                Some(Err(x_error)) => return Err(ReplyError::X(x_error).into()),
+                None => return Err(ReplyError::Disconnected.into()),
+            }
+        }
+        self.pending_replies
+            .lock()
+            .unwrap()
+            .remove(&sequence_number);
+
+        Ok(fonts)
+    }
+
+    pub async fn open_font(&mut self) -> io::Result<u32> {
+        self.refill_ids().await?;
+        let font_id = open_font(&mut self.request_buf, &mut self.id_generator)?;
+        self.send_void_unchecked().await?;
+
+        Ok(font_id)
+    }
+
+    pub async fn close_font(&mut self, font_id: u32) -> io::Result<()> {
+        close_font(&mut self.request_buf, font_id);
+        self.send_void_unchecked().await?;
+        self.id_generator.free(font_id);
+
+        Ok(())
+    }
+
+    pub async fn query_font(
+        &mut self,
+        fontable: u32,
+    ) -> Result<QueryFontReply, Box<dyn error::Error>> {
+        query_font(&mut self.request_buf, fontable);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(QueryFontReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn create_gc(
+        &mut self,
+        drawable: u32,
+        values: &[(CreateGcBits, u32)],
+    ) -> io::Result<GCId> {
+        self.refill_ids().await?;
+        let gc_id = create_gc(
+            &mut self.request_buf,
+            drawable,
+            values,
+            &mut self.id_generator,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok(gc_id)
+    }
+
+    // Creates a pixmap of `depth` on the screen of `drawable`, its
+    // contents are undefined until drawn into. The depth must be 1 or
+    // one of the depths of the screen.
+    pub async fn create_pixmap(
+        &mut self,
+        depth: u8,
+        drawable: u32,
+        width: u16,
+        height: u16,
+    ) -> io::Result<PixmapId> {
+        self.refill_ids().await?;
+        let pixmap_id = create_pixmap(
+            &mut self.request_buf,
+            depth,
+            drawable,
+            width,
+            height,
+            &mut self.id_generator,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok(pixmap_id)
+    }
+
+    pub async fn free_pixmap(&mut self, pixmap_id: PixmapId) -> io::Result<()> {
+        free_pixmap(&mut self.request_buf, pixmap_id);
+        self.send_void_unchecked().await?;
+        self.id_generator.free(pixmap_id);
+
+        Ok(())
+    }
+
+    pub async fn query_best_size(
+        &mut self,
+        class: SizeClass,
+        drawable: u32,
+        width: u16,
+        height: u16,
+    ) -> Result<QueryBestSizeReply, Box<dyn error::Error>> {
+        query_best_size(&mut self.request_buf, class, drawable, width, height);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(QueryBestSizeReply::from_bytes(&mut reply))
+    }
+
+    // The colormap can be used for windows of `visual`, see
+    // `WindowAttribute::Colormap`.
+    pub async fn create_colormap(
+        &mut self,
+        alloc: ColormapAlloc,
+        window_id: WindowId,
+        visual: VisualId,
+    ) -> io::Result<ColorMap> {
+        self.refill_ids().await?;
+        let colormap = create_colormap(
+            &mut self.request_buf,
+            alloc,
+            window_id,
+            visual,
+            &mut self.id_generator,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok(colormap)
+    }
+
+    pub async fn free_colormap(&mut self, colormap: ColorMap) -> io::Result<()> {
+        free_colormap(&mut self.request_buf, colormap);
+        self.send_void_unchecked().await?;
+        self.id_generator.free(colormap);
+
+        Ok(())
+    }
+
+    // Allocates a read-only colormap entry for the closest color the
+    // hardware supports, e.g. in the default colormap of the screen.
+    // The components range from 0 to 0xffff.
+    pub async fn alloc_color(
+        &mut self,
+        colormap: ColorMap,
+        red: u16,
+        green: u16,
+        blue: u16,
+    ) -> Result<AllocColorReply, Box<dyn error::Error>> {
+        alloc_color(&mut self.request_buf, colormap, red, green, blue);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(AllocColorReply::from_bytes(&mut reply))
+    }
+
+    // Creates a pixmap for the Tile of a GC in the size closest to the
+    // desired one which the server tiles with fastest. Returns the
+    // pixmap with its width and height, its contents are undefined
+    // until drawn into.
+    pub async fn create_tile_pixmap(
+        &mut self,
+        drawable: u32,
+        desired_width: u16,
+        desired_height: u16,
+        depth: u8,
+    ) -> Result<(PixmapId, u16, u16), Box<dyn error::Error>> {
+        let best_size = self
+            .query_best_size(SizeClass::Tile, drawable, desired_width, desired_height)
+            .await?;
+        self.refill_ids().await?;
+        let pixmap_id = create_pixmap(
+            &mut self.request_buf,
+            depth,
+            drawable,
+            best_size.width,
+            best_size.height,
+            &mut self.id_generator,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok((pixmap_id, best_size.width, best_size.height))
+    }
+
+    // uploads the bitmap into a new pixmap of depth 1, which can be
+    // used e.g. as Shape mask
+    pub async fn create_pixmap_from_bitmap(
+        &mut self,
+        drawable: u32,
+        bitmap: &Bitmap,
+    ) -> io::Result<PixmapId> {
+        self.refill_ids().await?;
+        let pixmap_id = create_pixmap(
+            &mut self.request_buf,
+            1,
+            drawable,
+            bitmap.width,
+            bitmap.height,
+            &mut self.id_generator,
+        )?;
+        self.send_void_unchecked().await?;
+
+        let gc_id = self
+            .create_gc(
+                pixmap_id,
+                &[(CreateGcBits::Foreground, 1), (CreateGcBits::Background, 0)],
+            )
+            .await?;
+
+        let mut data = Ordered::new(BytesMut::new(), self.byte_order());
+        bitmap.encode(&self.setup, &mut data);
+        self.put_image(
+            ImageFormat::Bitmap,
+            pixmap_id,
+            gc_id,
+            bitmap.width,
+            bitmap.height,
+            0,
+            0,
+            0,
+            1,
+            &data,
+        )
+        .await?;
+
+        self.free_gc(gc_id).await?;
+
+        Ok(pixmap_id)
+    }
+
+    // fails if a request of `request_length` words exceeds the maximum
+    // request length of the server
+    fn ensure_request_fits(&self, name: &str, request_length: u32) -> Result<(), RequestTooLong> {
+        if request_length > self.maximum_request_length {
+            return Err(RequestTooLong {
+                request: name.to_string(),
+                length: request_length,
+                maximum: self.maximum_request_length,
+            });
+        }
+
+        Ok(())
+    }
+
+    // Checks the requests in `request_buf` before they are written,
+    // whichever builder created them. Requests are dropped if one of
+    // them is too long.
+    fn ensure_buffered_requests_fit(&mut self) -> Result<(), RequestTooLong> {
+        let byte_order = self.byte_order();
+        let mut requests = &self.request_buf[..];
+        while requests.len() >= 4 {
+            let len = request_len(byte_order, requests);
+            if len > self.maximum_request_length {
+                let request_too_long = RequestTooLong {
+                    request: request_name(requests),
+                    length: len,
+                    maximum: self.maximum_request_length,
+                };
+                self.request_buf.clear();
+                return Err(request_too_long);
+            }
+            requests = &requests[(len as usize * 4).clamp(4, requests.len())..];
+        }
+
+        Ok(())
+    }
+
+    // Uploads the image `data`, encoded according to `format` and the
+    // image format of the setup, to `drawable`. The request must not
+    // exceed the maximum request length of the server, otherwise this
+    // fails, larger images have to be put in bands of rows or need
+    // BIG-REQUESTS, see `big_requests_enable`.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn put_image(
+        &mut self,
+        format: ImageFormat,
+        drawable: u32,
+        gc_id: GCId,
+        width: u16,
+        height: u16,
+        dst_x: i16,
+        dst_y: i16,
+        left_pad: u8,
+        depth: u8,
+        data: &[u8],
+    ) -> io::Result<()> {
+        self.ensure_request_fits("PutImage", big_request_len_words(24, data.len())?)?;
+        put_image(
+            &mut self.request_buf,
+            format,
+            drawable,
+            gc_id,
+            width,
+            height,
+            dst_x,
+            dst_y,
+            left_pad,
+            depth,
+            data,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Reads back the contents of a rectangle of `drawable`, e.g. for a
+    // screenshot of the root window.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_image(
+        &mut self,
+        format: ImageFormat,
+        drawable: u32,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+        plane_mask: u32,
+    ) -> Result<GetImageReply, Box<dyn error::Error>> {
+        get_image(
+            &mut self.request_buf,
+            format,
+            drawable,
+            x,
+            y,
+            width,
+            height,
+            plane_mask,
+        );
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetImageReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn free_gc(&mut self, gc_id: GCId) -> io::Result<()> {
+        free_gc(&mut self.request_buf, gc_id);
+        self.send_void_unchecked().await?;
+        self.id_generator.free(gc_id);
+
+        Ok(())
+    }
+
+    pub async fn clear_area(
+        &mut self,
+        exposures: bool,
+        window_id: WindowId,
+        x: i16,
+        y: i16,
+        width: u16,
+        height: u16,
+    ) -> io::Result<()> {
+        clear_area(
+            &mut self.request_buf,
+            exposures,
+            window_id,
+            x,
+            y,
+            width,
+            height,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn copy_area(
+        &mut self,
+        src: u32,
+        dst: u32,
+        gc_id: GCId,
+        src_x: i16,
+        src_y: i16,
+        dst_x: i16,
+        dst_y: i16,
+        width: u16,
+        height: u16,
+    ) -> io::Result<()> {
+        copy_area(
+            &mut self.request_buf,
+            src,
+            dst,
+            gc_id,
+            src_x,
+            src_y,
+            dst_x,
+            dst_y,
+            width,
+            height,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn poly_line(
+        &mut self,
+        coordinate_mode: CoordinateMode,
+        drawable: u32,
+        gc_id: GCId,
+        points: &[Point],
+    ) -> io::Result<()> {
+        self.ensure_request_fits("PolyLine", big_request_len_words(12, 4 * points.len())?)?;
+        poly_line(
+            &mut self.request_buf,
+            coordinate_mode,
+            drawable,
+            gc_id,
+            points,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn poly_segment(
+        &mut self,
+        drawable: u32,
+        gc_id: GCId,
+        segments: &[(Point, Point)],
+    ) -> io::Result<()> {
+        self.ensure_request_fits(
+            "PolySegment",
+            big_request_len_words(12, 8 * segments.len())?,
+        )?;
+        poly_segment(&mut self.request_buf, drawable, gc_id, segments)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn poly_rectangle(
+        &mut self,
+        drawable: u32,
+        gc_id: GCId,
+        rectangles: &[Rectangle],
+    ) -> io::Result<()> {
+        self.ensure_request_fits(
+            "PolyRectangle",
+            big_request_len_words(12, 8 * rectangles.len())?,
+        )?;
+        poly_rectangle(&mut self.request_buf, drawable, gc_id, rectangles)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn poly_arc(&mut self, drawable: u32, gc_id: GCId, arcs: &[Arc]) -> io::Result<()> {
+        self.ensure_request_fits("PolyArc", big_request_len_words(12, 12 * arcs.len())?)?;
+        poly_arc(&mut self.request_buf, drawable, gc_id, arcs)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn poly_fill_arc(
+        &mut self,
+        drawable: u32,
+        gc_id: GCId,
+        arcs: &[Arc],
+    ) -> io::Result<()> {
+        self.ensure_request_fits("PolyFillArc", big_request_len_words(12, 12 * arcs.len())?)?;
+        poly_fill_arc(&mut self.request_buf, drawable, gc_id, arcs)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn poly_fill_rectangle(
+        &mut self,
+        drawable: u32,
+        gc_id: GCId,
+        rectangles: &[Rectangle],
+    ) -> io::Result<()> {
+        self.ensure_request_fits(
+            "PolyFillRectangle",
+            big_request_len_words(12, 8 * rectangles.len())?,
+        )?;
+        poly_fill_rectangle(&mut self.request_buf, drawable, gc_id, rectangles)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn image_text_8(
+        &mut self,
+        window_id: WindowId,
+        gc_id: GCId,
+        x: i16,
+        y: i16,
+        text: &[u8],
+    ) -> io::Result<()> {
+        image_text_8(&mut self.request_buf, window_id, gc_id, x, y, text)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn image_text_16(
+        &mut self,
+        drawable: u32,
+        gc_id: GCId,
+        x: i16,
+        y: i16,
+        text: &[u16],
+    ) -> io::Result<()> {
+        image_text_16(&mut self.request_buf, drawable, gc_id, x, y, text)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn query_text_extents(
+        &mut self,
+        fontable: u32,
+        text: &[u16],
+    ) -> Result<QueryTextExtentsReply, Box<dyn error::Error>> {
+        query_text_extents(&mut self.request_buf, fontable, text)?;
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(QueryTextExtentsReply::from_bytes(&mut reply))
+    }
+
+    // ImageText8 is limited to 255 bytes, longer texts are split into
+    // several requests, each one starting where the previous chunk
+    // ended according to the font of `gc_id`
+    pub async fn draw_text(
+        &mut self,
+        drawable: u32,
+        gc_id: GCId,
+        mut x: i16,
+        y: i16,
+        text: &[u8],
+    ) -> Result<(), Box<dyn error::Error>> {
+        let mut chunks = text.chunks(u8::MAX.into()).peekable();
+        while let Some(chunk) = chunks.next() {
+            self.image_text_8(drawable, gc_id, x, y, chunk).await?;
+            if chunks.peek().is_some() {
+                let char2bs: Vec<u16> = chunk.iter().copied().map(u16::from).collect();
+                let extents = self.query_text_extents(gc_id, &char2bs).await?;
+                x = x.wrapping_add(extents.overall_width.try_into()?);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_extensions(&mut self) -> Result<ListExtensionsReply, Box<dyn error::Error>> {
+        list_extensions(&mut self.request_buf);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(ListExtensionsReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn query_extension(
+        &mut self,
+        extension_name: &[u8],
+    ) -> Result<QueryExtensionReply, Box<dyn error::Error>> {
+        query_extension(&mut self.request_buf, extension_name)?;
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(QueryExtensionReply::from_bytes(&mut reply))
+    }
+
+    // Names of the extensions the server supports. ListExtensions is
+    // only sent the first time, afterwards `query_extensions` doesn't
+    // query extensions which aren't in the list.
+    pub async fn available_extensions(&mut self) -> Result<Vec<String>, Box<dyn error::Error>> {
+        if let Some(available_extensions) = &self.available_extensions {
+            return Ok(available_extensions.clone());
+        }
+
+        let names: Vec<String> = self
+            .list_extensions()
+            .await?
+            .names
+            .into_iter()
+            .map(String::from)
+            .collect();
+        self.available_extensions = Some(names.clone());
+
+        Ok(names)
+    }
+
+    // All QueryExtension requests are sent before the first reply is
+    // awaited, so this costs a single round trip. After
+    // `available_extensions`, extensions the server doesn't advertise
+    // aren't queried at all.
+    pub async fn query_extensions(
+        &mut self,
+        extension_names: &[&str],
+    ) -> Result<HashMap<String, Option<ExtensionInfo>>, Box<dyn error::Error>> {
+        let mut extensions = HashMap::with_capacity(extension_names.len());
+        let mut cookies = Vec::with_capacity(extension_names.len());
+        for &extension_name in extension_names {
+            if let Some(available_extensions) = &self.available_extensions {
+                if !available_extensions
+                    .iter()
+                    .any(|available| available == extension_name)
+                {
+                    self.extensions.insert(extension_name.to_string(), None);
+                    extensions.insert(extension_name.to_string(), None);
+                    continue;
+                }
+            }
+            query_extension(&mut self.request_buf, extension_name.as_bytes())?;
+            cookies.push((extension_name, self.send_request().await?));
+        }
+
+        for (extension_name, cookie) in cookies {
+            let reply = QueryExtensionReply::from_bytes(&mut cookie.reply().await?);
+            let extension_info = reply.present.then_some(ExtensionInfo {
+                major_opcode: reply.major_opcode,
+                first_event: reply.first_event,
+                first_error: reply.first_error,
+            });
+            self.extensions
+                .insert(extension_name.to_string(), extension_info);
+            extensions.insert(extension_name.to_string(), extension_info);
+        }
+
+        Ok(extensions)
+    }
+
+    // The events as a stream, which ends once the server closed the
+    // connection. There is no backpressure: the reader never waits for
+    // the consumer, so replies keep arriving while events are not
+    // consumed, but a slow consumer lets the events queue up in memory.
+    pub fn events(&mut self) -> impl Stream<Item = DecodedEvent> + '_ {
+        EventStream { connection: self }
+    }
+
+    pub async fn next_event(&mut self) -> Option<DecodedEvent> {
+        if let Some(event) = self.pending_events.pop_front() {
+            return Some(event);
+        }
+
+        self.events.recv().await
+    }
+
+    // waits for the first event accepted by `matches`, other events
+    // are kept and returned by `next_event` later
+    pub async fn wait_for_event(
+        &mut self,
+        matches: impl Fn(&DecodedEvent) -> bool,
+    ) -> Option<DecodedEvent> {
+        if let Some(index) = self.pending_events.iter().position(&matches) {
+            return self.pending_events.remove(index);
+        }
+
+        while let Some(event) = self.events.recv().await {
+            if matches(&event) {
+                return Some(event);
+            }
+            self.pending_events.push_back(event);
+        }
+
+        None
+    }
+
+    // drops PropertyNotify events of `window_id`, e.g. of a helper
+    // window which has been destroyed
+    pub fn discard_property_events(&mut self, window_id: WindowId) {
+        while let Ok(event) = self.events.try_recv() {
+            self.pending_events.push_back(event);
+        }
+        self.pending_events.retain(|event| {
+            !matches!(event, DecodedEvent::PropertyNotify(property_notify)
+                if property_notify.window == window_id)
+        });
+    }
+
+    pub async fn change_window_attributes(
+        &mut self,
+        window_id: WindowId,
+        values: &[WindowAttribute],
+    ) -> io::Result<()> {
+        change_window_attributes(&mut self.request_buf, window_id, values)?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn select_input(
+        &mut self,
+        window_id: WindowId,
+        event_mask: BitFlags<Event>,
+    ) -> io::Result<()> {
+        select_input(&mut self.request_buf, window_id, event_mask);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Like `select_input`, but a mask with exclusive events is checked,
+    // see `EXCLUSIVE_EVENTS`, and fails with an `EventMaskConflict` if
+    // the server rejects it.
+    pub async fn select_input_checked(
+        &mut self,
+        window_id: WindowId,
+        event_mask: BitFlags<Event>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let exclusive_events = event_mask & EXCLUSIVE_EVENTS;
+        select_input(&mut self.request_buf, window_id, event_mask);
+        if exclusive_events.is_empty() {
+            self.send_void_unchecked().await?;
+        } else {
+            match self.send_void_checked().await {
+                Err(error)
+                    if error
+                        .downcast_ref::<XError>()
+                        .is_some_and(|x_error| x_error.error_code() == Some(ErrorCode::Access)) =>
+                {
+                    return Err(EventMaskConflict {
+                        window: window_id,
+                        events: exclusive_events,
+                    }
+                    .into());
+                }
+                result => {
+                    result?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_do_not_propagate_mask(
+        &mut self,
+        window_id: WindowId,
+        do_not_propagate_mask: DeviceEventMask,
+    ) -> io::Result<()> {
+        set_do_not_propagate_mask(&mut self.request_buf, window_id, do_not_propagate_mask);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn intern_atom(
+        &mut self,
+        only_if_exists: bool,
+        name: &[u8],
+    ) -> Result<Atom, Box<dyn error::Error>> {
+        intern_atom(&mut self.request_buf, only_if_exists, name)?;
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(InternAtomReply::from_bytes(&mut reply).atom)
+    }
+
+    pub async fn get_atom_name(
+        &mut self,
+        atom: Atom,
+    ) -> Result<GetAtomNameReply, Box<dyn error::Error>> {
+        get_atom_name(&mut self.request_buf, atom);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetAtomNameReply::from_bytes(&mut reply)?)
+    }
+
+    pub async fn send_event(
+        &mut self,
+        propagate: bool,
+        destination: WindowId,
+        event_mask: BitFlags<Event>,
+        event: &[u8; 32],
+    ) -> io::Result<()> {
+        send_event(
+            &mut self.request_buf,
+            propagate,
+            destination,
+            event_mask,
+            event,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Sends a synthetic KeyPress or KeyRelease of `keycode` with the
+    // modifiers and buttons of `state` to the clients selecting it on
+    // `window_id`, which doesn't require the XTEST extension. The
+    // server marks the event as sent by SendEvent and many clients,
+    // e.g. xterm by default, ignore such events, so this only works
+    // with applications which cooperate.
+    pub async fn send_key(
+        &mut self,
+        window_id: WindowId,
+        keycode: u8,
+        press: bool,
+        state: u16,
+    ) -> io::Result<()> {
+        let root_window = self.default_screen().window;
+        let event = key_event(
+            self.byte_order(),
+            press,
+            keycode,
+            root_window,
+            window_id,
+            state,
+        );
+        let event_mask = if press {
+            Event::KeyPress
+        } else {
+            Event::KeyRelease
+        };
+        self.send_event(false, window_id, event_mask.into(), &event)
+            .await
+    }
+
+    // Asks the window manager to add, remove or toggle one or two
+    // states of the mapped `window_id`, like _NET_WM_STATE_FULLSCREEN
+    // or both _NET_WM_STATE_MAXIMIZED_VERT and
+    // _NET_WM_STATE_MAXIMIZED_HORZ.
+    pub async fn set_wm_state(
+        &mut self,
+        window_id: WindowId,
+        action: WmStateAction,
+        atoms: &[Atom],
+    ) -> Result<(), Box<dyn error::Error>> {
+        let (first, second) = match *atoms {
+            [first] => (first, 0),
+            [first, second] => (first, second),
+            _ => return Err(format!("expected one or two states, got {}", atoms.len()).into()),
+        };
+        let net_wm_state = self.intern_atom(false, b"_NET_WM_STATE").await?;
+        // the last but one value is the source indication, 1 means
+        // normal application
+        let event = encode_client_message(
+            self.byte_order(),
+            window_id,
+            net_wm_state,
+            &ClientMessageData::Data32([action as u32, first, second, 1, 0]),
+        );
+        let root_window = self.default_screen().window;
+        self.send_event(
+            false,
+            root_window,
+            make_bitflags!(Event::{SubstructureNotify | SubstructureRedirect}),
+            &event,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn change_property(
+        &mut self,
+        mode: PropertyMode,
+        window_id: WindowId,
+        property: Atom,
+        type_: Atom,
+        format: u8,
+        data: &[u8],
+    ) -> io::Result<()> {
+        self.ensure_request_fits("ChangeProperty", big_request_len_words(24, data.len())?)?;
+        change_property(
+            &mut self.request_buf,
+            mode,
+            window_id,
+            property,
+            type_,
+            format,
+            data,
+        )?;
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn get_property(
+        &mut self,
+        delete: bool,
+        window_id: WindowId,
+        property: Atom,
+        type_: Atom,
+        long_offset: u32,
+        long_length: u32,
+    ) -> Result<GetPropertyReply, Box<dyn error::Error>> {
+        get_property(
+            &mut self.request_buf,
+            delete,
+            window_id,
+            property,
+            type_,
+            long_offset,
+            long_length,
+        );
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetPropertyReply::from_bytes(&mut reply)?)
+    }
+
+    // Reads the whole value of a property of any size, e.g. the icons
+    // of _NET_WM_ICON, in chunks of `PROPERTY_CHUNK_LEN` units of 4
+    // bytes. Returns an empty value if the property doesn't exist or
+    // isn't of type `type_`.
+    pub async fn get_property_full(
+        &mut self,
+        window_id: WindowId,
+        property: Atom,
+        type_: Atom,
+    ) -> Result<Vec<u8>, Box<dyn error::Error>> {
+        let mut value = Vec::new();
+        // in units of 4 bytes, like long-offset
+        let mut long_offset = 0;
+        loop {
+            let reply = self
+                .get_property(
+                    false,
+                    window_id,
+                    property,
+                    type_,
+                    long_offset,
+                    PROPERTY_CHUNK_LEN,
+                )
+                .await?;
+            value.extend_from_slice(&reply.value);
+            if reply.bytes_after == 0 || reply.value.is_empty() {
+                break;
+            }
+            // only the last chunk can end within 4 bytes
+            long_offset += u32::try_from(reply.value.len() / 4)?;
+        }
+
+        Ok(value)
+    }
+
+    pub async fn set_selection_owner(
+        &mut self,
+        owner: WindowId,
+        selection: Atom,
+        time: u32,
+    ) -> io::Result<()> {
+        set_selection_owner(&mut self.request_buf, owner, selection, time);
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    pub async fn get_selection_owner(
+        &mut self,
+        selection: Atom,
+    ) -> Result<GetSelectionOwnerReply, Box<dyn error::Error>> {
+        get_selection_owner(&mut self.request_buf, selection);
+        let mut reply = self.send_request().await?.reply().await?;
+
+        Ok(GetSelectionOwnerReply::from_bytes(&mut reply))
+    }
+
+    pub async fn convert_selection(
+        &mut self,
+        requestor: WindowId,
+        selection: Atom,
+        target: Atom,
+        property: Atom,
+        time: u32,
+    ) -> io::Result<()> {
+        convert_selection(
+            &mut self.request_buf,
+            requestor,
+            selection,
+            target,
+            property,
+            time,
+        );
+        self.send_void_unchecked().await?;
+
+        Ok(())
+    }
+
+    // Receives the chunks of an incremental transfer into `property` of
+    // `window_id`. The window must have selected PropertyChange events
+    // before the INCR property was deleted, which starts the transfer.
+    pub async fn read_incr_property(
+        &mut self,
+        window_id: WindowId,
+        property: Atom,
+        size_hint: usize,
+    ) -> Result<Vec<u8>, Box<dyn error::Error>> {
+        let mut transfer = IncrTransfer::new(size_hint);
+        while !transfer.is_complete() {
+            self.wait_for_event(|event| {
+                matches!(event, DecodedEvent::PropertyNotify(property_notify)
+                    if property_notify.window == window_id
+                        && property_notify.atom == property
+                        && property_notify.state == PropertyState::NewValue)
+            })
+            .await
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+
+            let reply = self
+                .get_property(true, window_id, property, 0, 0, u32::MAX / 4)
+                .await?;
+            transfer.push_chunk(&reply);
+        }
+
+        Ok(transfer.into_data())
+    }
+
+    // Asks the owner of the selection, e.g. the CLIPBOARD atom, to
+    // convert it to UTF8_STRING and store it in a property of a hidden
+    // window. Returns `None` if there is no owner or the owner can't
+    // convert the selection.
+    pub async fn get_clipboard_text(
+        &mut self,
+        clipboard_atom: Atom,
+    ) -> Result<Option<String>, Box<dyn error::Error>> {
+        let root = self.default_screen().root();
+        // never mapped, so its geometry doesn't matter
+        let rectangle = Rectangle {
+            x: 0,
+            y: 0,
+            width: 1,
+            height: 1,
+        };
+        let window_id = self.create_simple_window(&root, rectangle, 0, &[]).await?;
+        let text = self.read_clipboard_text(window_id, clipboard_atom).await;
+
+        // destroyed on every path, the helper window would leak otherwise
+        self.destroy_window(window_id).await?;
+        self.discard_property_events(window_id);
+
+        text
+    }
+
+    // Converts the selection to UTF8_STRING on `window_id`, which
+    // `get_clipboard_text` creates for this and destroys afterwards.
+    async fn read_clipboard_text(
+        &mut self,
+        window_id: WindowId,
+        clipboard_atom: Atom,
+    ) -> Result<Option<String>, Box<dyn error::Error>> {
+        self.select_input(window_id, make_bitflags!(Event::{PropertyChange}))
+            .await?;
+
+        intern_atom(&mut self.request_buf, false, b"UTF8_STRING")?;
+        let utf8_string = self.send_request().await?;
+        intern_atom(&mut self.request_buf, false, b"XCLIENT_SELECTION")?;
+        let property = self.send_request().await?;
+        intern_atom(&mut self.request_buf, false, b"INCR")?;
+        let incr = self.send_request().await?;
+        let utf8_string = InternAtomReply::from_bytes(&mut utf8_string.reply().await?).atom;
+        let property = InternAtomReply::from_bytes(&mut property.reply().await?).atom;
+        let incr = InternAtomReply::from_bytes(&mut incr.reply().await?).atom;
+
+        self.convert_selection(window_id, clipboard_atom, utf8_string, property, 0)
+            .await?;
+
+        let selection_notify = self
+            .wait_for_event(|event| {
+                matches!(event, DecodedEvent::SelectionNotify(selection_notify)
+                    if selection_notify.requestor == window_id)
+            })
+            .await;
+        let Some(DecodedEvent::SelectionNotify(selection_notify)) = selection_notify else {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        };
+        let text = if selection_notify.property.is_none() {
+            // the selection couldn't be converted
+            None
+        } else {
+            let reply = self
+                .get_property(true, window_id, property, 0, 0, u32::MAX / 4)
+                .await?;
+            if reply.type_ == incr {
+                // deleting the INCR property above started the transfer
+                let size_hint = reply.value.get(..4).map_or(0, |size| {
+                    self.byte_order().card32(size.try_into().unwrap()) as usize
+                });
+                let data = self
+                    .read_incr_property(window_id, property, size_hint)
+                    .await?;
+                Some(String::from_utf8_lossy(&data).into_owned())
+            } else {
+                Some(String::from_utf8_lossy(&reply.value).into_owned())
+            }
+        };
+
+        Ok(text)
+    }
+
+    // Sets WM_CLASS, which window managers and taskbars identify the
+    // application by: the instance name, usually the program name, and
+    // the class name, each terminated by a null byte.
+    pub async fn set_wm_class(
+        &mut self,
+        window_id: WindowId,
+        instance: &str,
+        class: &str,
+    ) -> io::Result<()> {
+        let mut data = Vec::with_capacity(instance.len() + class.len() + 2);
+        data.extend_from_slice(instance.as_bytes());
+        data.push(0);
+        data.extend_from_slice(class.as_bytes());
+        data.push(0);
+
+        self.change_property(
+            PropertyMode::Replace,
+            window_id,
+            ATOM_WM_CLASS,
+            ATOM_STRING,
+            8,
+            &data,
+        )
+        .await
+    }
+
+    // Title of the window, preferably from _NET_WM_NAME encoded as
+    // UTF-8, otherwise from WM_NAME encoded as Latin-1. Returns `None`
+    // if neither is set in one of these encodings.
+    pub async fn get_window_title(
+        &mut self,
+        window_id: WindowId,
+    ) -> Result<Option<String>, Box<dyn error::Error>> {
+        // only if they exist, otherwise no window can have the property
+        intern_atom(&mut self.request_buf, true, b"_NET_WM_NAME")?;
+        let net_wm_name = self.send_request().await?;
+        intern_atom(&mut self.request_buf, true, b"UTF8_STRING")?;
+        let utf8_string = self.send_request().await?;
+        let net_wm_name = InternAtomReply::from_bytes(&mut net_wm_name.reply().await?).atom;
+        let utf8_string = InternAtomReply::from_bytes(&mut utf8_string.reply().await?).atom;
+
+        if net_wm_name != 0 && utf8_string != 0 {
+            let reply = self
+                .get_property(false, window_id, net_wm_name, utf8_string, 0, u32::MAX / 4)
+                .await?;
+            if reply.format == 8 && reply.type_ == utf8_string {
+                return Ok(Some(String::from_utf8_lossy(&reply.value).into_owned()));
+            }
+        }
+
+        let reply = self
+            .get_property(false, window_id, ATOM_WM_NAME, ATOM_STRING, 0, u32::MAX / 4)
+            .await?;
+        if reply.format == 8 && reply.type_ == ATOM_STRING {
+            // Latin-1 maps one to one to the first 256 code points
+            return Ok(Some(reply.value.iter().copied().map(char::from).collect()));
+        }
+
+        Ok(None)
+    }
+
+    // Icons of the window in all sizes it provides via _NET_WM_ICON,
+    // which is empty if it provides none.
+    pub async fn get_window_icons(
+        &mut self,
+        window_id: WindowId,
+    ) -> Result<Vec<IconImage>, Box<dyn error::Error>> {
+        // only if it exists, otherwise no window can have the property
+        let net_wm_icon = self.intern_atom(true, b"_NET_WM_ICON").await?;
+        if net_wm_icon == 0 {
+            return Ok(Vec::new());
+        }
+        let value = self
+            .get_property_full(window_id, net_wm_icon, ATOM_CARDINAL)
+            .await?;
+
+        Ok(IconImage::from_property(Ordered::new(
+            &value[..],
+            self.byte_order(),
+        )))
+    }
+
+    // Lets the reader dispatch the events of an extension, which must
+    // have been queried with `query_extensions` before. Returns false
+    // if the extension isn't present.
+    pub fn register_extension_events(
+        &mut self,
+        extension_name: &str,
+        decoder: Box<dyn ExtensionEventDecoder>,
+    ) -> bool {
+        if let Some(Some(extension_info)) = self.extensions.get(extension_name) {
+            self.extension_event_decoders
+                .lock()
+                .unwrap()
+                .push((extension_info.first_event, decoder));
+            true
+        } else {
+            false
+        }
+    }
+
+    // Sends whatever hasn't been sent yet. Requests are written as they
+    // are issued, only a write which has been cancelled, e.g. by a
+    // timeout, or a pending UngrabServer leaves something behind.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.send_pending_ungrab_server().await?;
+        self.write_stream
+            .write_all_buf(&mut self.request_buf)
+            .await?;
+        self.write_stream.flush().await
+    }
+
+    // sends all requests written so far and closes the connection,
+    // which makes the server free all resources of this client
+    pub async fn disconnect(mut self) -> io::Result<()> {
+        self.flush().await?;
+        self.write_stream.shutdown().await
+    }
+}
+
+impl Drop for Connection {
+    // Dropping the connection can't wait for unsent requests to be
+    // written, so they are written by a detached task as a best
+    // effort. Only awaiting `disconnect` guarantees they are sent.
+    fn drop(&mut self) {
+        self.reader.abort();
+
+        if self.ungrab_server_pending.swap(false, Ordering::Relaxed) {
+            ungrab_server(&mut self.request_buf);
+        }
+        if self.request_buf.is_empty() {
+            return;
+        }
+
+        #[cfg(debug_assertions)]
+        log::warn!(
+            "connection dropped with {} bytes of unsent requests, await disconnect to send them",
+            self.request_buf.len()
+        );
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let mut write_stream =
+                mem::replace(&mut self.write_stream, Box::new(tokio::io::sink()));
+            let mut request_buf = mem::take(&mut self.request_buf);
+            runtime.spawn(async move {
+                if write_stream.write_all_buf(&mut request_buf).await.is_ok() {
+                    let _ = write_stream.shutdown().await;
+                }
+            });
+        }
+    }
+}
+
+// see `Connection::events`
+struct EventStream<'a> {
+    connection: &'a mut Connection,
+}
+
+impl Stream for EventStream<'_> {
+    type Item = DecodedEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<DecodedEvent>> {
+        // events kept by `wait_for_event` come first
+        if let Some(event) = self.connection.pending_events.pop_front() {
+            return Poll::Ready(Some(event));
+        }
+
+        self.connection.events.poll_recv(cx)
+    }
+}
+
+// Pointer motion of a window, which keeps the pointer in its center,
+// the classic mouse look of games. Created by
+// `Connection::pointer_relative_mode`.
+pub struct RelativePointer {
+    window_id: WindowId,
+    center: (i16, i16),
+    // position of the last motion event, the deltas are relative to it
+    last: (i16, i16),
+    // a warp to the center has been sent, but its MotionNotify hasn't
+    // arrived yet
+    warp_pending: bool,
+    // position of the pointer before, relative to its root window
+    origin: (WindowId, i16, i16),
+}
+
+impl RelativePointer {
+    // Returns the motion since the last call if `event` is a
+    // MotionNotify of the window and warps the pointer back to the
+    // center. The MotionNotify caused by the warp itself is filtered,
+    // motion events which arrive before it are still relative to the
+    // position before the warp.
+    pub async fn motion(
+        &mut self,
+        connection: &mut Connection,
+        event: &DecodedEvent,
+    ) -> io::Result<Option<(i16, i16)>> {
+        let DecodedEvent::MotionNotify(motion) = event else {
+            return Ok(None);
+        };
+        if motion.event != self.window_id {
+            return Ok(None);
+        }
+
+        let position = (motion.event_x, motion.event_y);
+        if self.warp_pending && position == self.center {
+            self.warp_pending = false;
+            self.last = self.center;
+            return Ok(None);
+        }
+
+        let delta = (
+            position.0.saturating_sub(self.last.0),
+            position.1.saturating_sub(self.last.1),
+        );
+        self.last = position;
+        if !self.warp_pending && position != self.center {
+            connection
+                .warp_pointer(0, self.window_id, 0, 0, 0, 0, self.center.0, self.center.1)
+                .await?;
+            self.warp_pending = true;
+        }
+
+        Ok(Some(delta))
+    }
+
+    // moves the pointer back to where it was before the relative mode
+    pub async fn exit(self, connection: &mut Connection) -> io::Result<()> {
+        let (root, x, y) = self.origin;
+        connection.warp_pointer(0, root, 0, 0, 0, 0, x, y).await
+    }
+}
+
+// Click to focus for a window manager, which still passes the click
+// on to the clicked window.
+//
+// Clicks on each unfocused window are grabbed passively in
+// synchronous pointer mode, so on a click the server activates the
+// grab, reports the ButtonPress to the window manager and freezes the
+// pointer, i.e. queues all further pointer events of all clients.
+// After focusing the window, AllowEvents with ReplayPointer releases
+// the grab and replays the ButtonPress as if the grab didn't exist,
+// so the window receives the click. Until then the pointer stays
+// frozen, hence `handle_event` must be called right away for every
+// event. The focused window doesn't need the grab anymore, it's moved
+// to the window which had the focus before.
+#[derive(Debug, Default)]
+pub struct ClickToFocusHandler {
+    focused: Option<WindowId>,
+}
+
+impl ClickToFocusHandler {
+    // grabs the clicks of an unfocused window, e.g. a newly managed
+    // client window
+    pub async fn manage(
+        &mut self,
+        connection: &mut Connection,
+        window_id: WindowId,
+    ) -> io::Result<()> {
+        let button_press = u16::try_from(BitFlags::from(Event::ButtonPress).bits())
+            .expect("pointer events fit into 16 bits");
+        connection
+            .grab_button(
+                false,
+                window_id,
+                button_press,
+                GrabMode::Synchronous,
+                GrabMode::Asynchronous,
+                0,
+                0,
+                ANY_BUTTON,
+                ANY_MODIFIER,
+            )
+            .await
+    }
+
+    // Focuses the window if `event` is a ButtonPress of a grab and
+    // replays the click. Returns the newly focused window.
+    pub async fn handle_event(
+        &mut self,
+        connection: &mut Connection,
+        event: &DecodedEvent,
+    ) -> io::Result<Option<WindowId>> {
+        let DecodedEvent::ButtonPress(button_press) = event else {
+            return Ok(None);
+        };
+        // the event of an activated passive grab is reported to the
+        // grab window
+        let window_id = button_press.event;
+        if self.focused == Some(window_id) {
+            return Ok(None);
+        }
+
+        // the timestamp of the event makes sure the requests apply to
+        // this click and not to a later one
+        connection
+            .set_input_focus(RevertTo::PointerRoot, window_id, button_press.time)
+            .await?;
+        connection
+            .allow_events(AllowEventsMode::ReplayPointer, button_press.time)
+            .await?;
+
+        connection
+            .ungrab_button(ANY_BUTTON, window_id, ANY_MODIFIER)
+            .await?;
+        if let Some(previous) = self.focused.replace(window_id) {
+            self.manage(connection, previous).await?;
+        }
+
+        Ok(Some(window_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use tokio::io::DuplexStream;
+
+    fn assert_unique(ids: &[u32]) {
+        let unique: HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len(), "ids handed out twice: {ids:x?}");
+    }
+
+    // a successful setup reply with one 1920x1080 screen whose root
+    // window is `root`
+    fn setup_reply(resource_id_base: u32, root: WindowId) -> Vec<u8> {
+        let mut reply = Ordered::new(BytesMut::new(), ByteOrder::LsbFirst);
+        reply.put_u8(1); // success
+        reply.put_u8(0); // unused
+        reply.put_card16(11); // protocol major version
+        reply.put_card16(0); // protocol minor version
+        reply.put_card16(19); // length of additional data
+        reply.put_card32(1); // release number
+        reply.put_card32(resource_id_base); // resource id base
+        reply.put_card32(0x001f_ffff); // resource id mask
+        reply.put_card32(0); // motion buffer size
+        reply.put_card16(4); // length of vendor
+        reply.put_card16(0xffff); // maximum request length
+        reply.put_u8(1); // number of screens
+        reply.put_u8(0); // number of formats
+        reply.put_u8(0); // image byte order
+        reply.put_u8(0); // bitmap format bit order
+        reply.put_u8(32); // bitmap format scanline unit
+        reply.put_u8(32); // bitmap format scanline pad
+        reply.put_u8(8); // min keycode
+        reply.put_u8(255); // max keycode
+        reply.put_card32(0); // unused
+        reply.put_slice(b"fake"); // vendor
+        reply.put_card32(root); // root
+        reply.put_card32(0x20); // default colormap
+        reply.put_card32(0x00ff_ffff); // white pixel
+        reply.put_card32(0); // black pixel
+        reply.put_card32(0); // current input masks
+        reply.put_card16(1920); // width in pixels
+        reply.put_card16(1080); // height in pixels
+        reply.put_card16(508); // width in millimeters
+        reply.put_card16(285); // height in millimeters
+        reply.put_card16(1); // min installed maps
+        reply.put_card16(1); // max installed maps
+        reply.put_card32(0x21); // root visual
+        reply.put_u8(0); // backing stores
+        reply.put_u8(0); // save unders
+        reply.put_u8(24); // root depth
+        reply.put_u8(0); // number of allowed depths
+
+        reply.to_vec()
+    }
+
+    // answers the setup and a single GetInputFocus with `focus`
+    async fn serve_input_focus(mut server: DuplexStream, resource_id_base: u32, focus: WindowId) {
+        let mut request = [0; 12];
+        server.read_exact(&mut request).await.unwrap();
+        server
+            .write_all(&setup_reply(resource_id_base, 0x100))
+            .await
+            .unwrap();
+
+        let mut request = [0; 4];
+        server.read_exact(&mut request).await.unwrap();
+        assert_eq!(request[0], Opcodes::GetInputFocus as u8);
+        let mut reply = Ordered::new(BytesMut::new(), ByteOrder::LsbFirst);
+        reply.put_u8(1); // reply
+        reply.put_u8(2); // revert-to Parent
+        reply.put_card16(1); // sequence number
+        reply.put_card32(0); // reply length
+        reply.put_card32(focus); // focus
+        reply.put_bytes(0, 20); // unused
+        server.write_all(&reply).await.unwrap();
+    }
+
+    #[test]
+    fn id_generator_hands_out_every_id_of_a_tiny_mask() {
+        let ids: Vec<_> = IdGenerator::new(0x0040_0000, 0x1F).collect();
+
+        assert_eq!(ids.len(), 0x1F);
+        assert_eq!(ids.first(), Some(&0x0040_0001));
+        assert_eq!(ids.last(), Some(&0x0040_001F));
+        assert_unique(&ids);
+    }
+
+    #[test]
+    fn id_generator_steps_by_the_lowest_bit_of_the_mask() {
+        let ids: Vec<_> = IdGenerator::new(0x0040_0000, 0x1F0).collect();
+
+        assert_eq!(ids.len(), 0x1F);
+        assert!(ids.iter().all(|id| id & !0x1F0 == 0x0040_0000));
+        assert_eq!(ids.last(), Some(&0x0040_01F0));
+        assert_unique(&ids);
+    }
+
+    #[test]
+    fn id_generator_reuses_freed_ids() {
+        let mut id_generator = IdGenerator::new(0x0040_0000, 0x1F);
+        let ids: Vec<_> = id_generator.by_ref().collect();
+        for &id in &ids {
+            id_generator.free(id);
+        }
+
+        let mut reused: Vec<_> = id_generator.by_ref().collect();
+        reused.sort_unstable();
+        assert_eq!(reused, ids);
+        assert_eq!(id_generator.next(), None);
+    }
+
+    #[test]
+    fn id_generator_ignores_ids_it_did_not_hand_out() {
+        let mut id_generator = IdGenerator::new(0x0040_0000, 0x1F);
+        let id = id_generator.next().unwrap();
+
+        // a window of another client, e.g. destroyed by a window manager
+        id_generator.free(0x0060_0001);
+        // an id of the mask which wasn't handed out yet
+        id_generator.free(0x0040_0005);
+        // freed twice
+        id_generator.free(id);
+        id_generator.free(id);
+
+        let ids: Vec<_> = id_generator.collect();
+        assert_eq!(ids.len(), 0x1F);
+        assert_eq!(ids.iter().filter(|&&other| other == id).count(), 1);
+        assert!(!ids.contains(&0x0060_0001));
+        assert_unique(&ids);
+    }
+
+    #[test]
+    fn id_generator_reuses_ids_of_xc_misc_ranges() {
+        let mut id_generator = IdGenerator::new(0x0040_0000, 0x1F);
+        let first = id_generator.next().unwrap();
+        while id_generator.next().is_some() {}
+        assert!(id_generator.is_exhausted());
+
+        assert!(!id_generator.set_range(0, 1));
+        assert!(!id_generator.set_range(0x0040_0003, 0));
+        assert!(id_generator.set_range(0x0040_0003, 2));
+        let granted: Vec<_> = id_generator.by_ref().collect();
+        assert_eq!(granted, [0x0040_0003, 0x0040_0004]);
+
+        id_generator.free(0x0040_0004);
+        id_generator.free(first);
+        assert_eq!(id_generator.next(), Some(first));
+        assert_eq!(id_generator.next(), Some(0x0040_0004));
+        assert_eq!(id_generator.next(), None);
+    }
+
+    #[test]
+    fn incr_transfer_caps_the_announced_size() {
+        let transfer = IncrTransfer::new(u32::MAX as usize);
+
+        assert!(transfer.data.capacity() <= INCR_PREALLOCATION_LIMIT);
+    }
+
+    #[test]
+    fn extension_events_are_decoded_by_their_decoder() {
+        let decoders = ExtensionEventDecoders::default();
+        decoders
+            .lock()
+            .unwrap()
+            .push((64, Box::new(ShapeExtension::new(129))));
+        let mut event = vec![0, 5, 0, 1, 0, 0x40, 0, 0xff, 0xff, 2, 0, 30, 0, 40, 0];
+        event.extend_from_slice(&1234u32.to_le_bytes());
+        event.push(1);
+        event.resize(31, 0);
+
+        let mut buf = Ordered::new(&event[..], ByteOrder::LsbFirst);
+        let decoded = decode_extension_event(64, &mut buf, &decoders).map(DecodedEvent::Extension);
+        assert_eq!(buf.remaining(), 0);
+        let shape_notify = decoded
+            .as_ref()
+            .and_then(DecodedEvent::extension_event::<ShapeNotifyEvent>)
+            .unwrap();
+        assert!(matches!(shape_notify.kind, ShapeKind::Bounding));
+        assert_eq!(shape_notify.window, 0x0040_0001);
+        assert_eq!((shape_notify.x, shape_notify.y), (-1, 2));
+        assert_eq!((shape_notify.width, shape_notify.height), (30, 40));
+        assert_eq!(shape_notify.server_time, 1234);
+        assert!(shape_notify.shaped);
+
+        let mut buf = Ordered::new(&event[..], ByteOrder::LsbFirst);
+        assert!(decode_extension_event(65, &mut buf, &decoders).is_none());
+    }
+
+    #[tokio::test]
+    async fn setup_reports_the_reason_for_authentication() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        // status 2, the reason padded to 8 words of additional data
+        let mut reply = vec![2, 0, 0, 0, 0, 0, 8, 0];
+        reply.extend_from_slice(b"MIT-MAGIC-COOKIE-1\0\0\0\0\0\0\0\0\0\0\0\0\0\0");
+        server.write_all(&reply).await.unwrap();
+
+        let Err(ConnectionError::AuthRequired { reason }) = Connection::from_stream(client).await
+        else {
+            panic!("authentication not required");
+        };
+        assert_eq!(reason, "MIT-MAGIC-COOKIE-1");
+    }
+
+    #[tokio::test]
+    async fn pending_replies_fail_once_the_server_closes_the_connection() {
+        let (client, mut server) = tokio::io::duplex(1024);
+        let server = tokio::spawn(async move {
+            let mut request = [0; 12];
+            server.read_exact(&mut request).await.unwrap();
+            server
+                .write_all(&setup_reply(0x0040_0000, 0x100))
+                .await
+                .unwrap();
+            // GetInputFocus, which is never answered
+            let mut request = [0; 4];
+            server.read_exact(&mut request).await.unwrap();
+        });
+        let mut connection = Connection::from_stream(client).await.unwrap();
+
+        assert!(connection.get_input_focus().await.is_err());
+        // the reader task ended and with it the events
+        assert!(connection.next_event().await.is_none());
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn connections_are_independent() {
+        let (first_client, first_server) = tokio::io::duplex(1024);
+        let (second_client, second_server) = tokio::io::duplex(1024);
+        let first_server = tokio::spawn(serve_input_focus(first_server, 0x0040_0000, 0x0040_0001));
+        let second_server =
+            tokio::spawn(serve_input_focus(second_server, 0x0060_0000, 0x0060_0001));
+
+        let (first, second) = tokio::join!(
+            Connection::from_stream(first_client),
+            Connection::from_stream(second_client)
+        );
+        let (mut first, mut second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.id_generator.next(), Some(0x0040_0001));
+        assert_eq!(second.id_generator.next(), Some(0x0060_0001));
+
+        let (first_focus, second_focus) =
+            tokio::join!(first.get_input_focus(), second.get_input_focus());
+        assert_eq!(first_focus.unwrap().focus, Focus::Window(0x0040_0001));
+        assert_eq!(second_focus.unwrap().focus, Focus::Window(0x0060_0001));
+
+        first_server.await.unwrap();
+        second_server.await.unwrap();
+    }
+}
@@ -0,0 +1,4176 @@
+mod big_requests;
+mod event;
+mod shape;
+mod xc_misc;
+mod xv;
+
+use ascii::AsciiString;
+use bytes::{Buf, BufMut, Bytes};
+use enumflags2::{bitflags, make_bitflags, BitFlags};
+use num_traits::FromPrimitive;
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+use std::error;
+use std::fmt;
+use std::io;
+use std::iter::Iterator;
+use std::num::TryFromIntError;
+use std::ops::{Deref, DerefMut};
+use std::vec::Vec;
+
+pub use self::big_requests::*;
+pub use self::event::*;
+pub use self::shape::*;
+pub use self::xc_misc::*;
+pub use self::xv::*;
+
+#[derive(Debug, num_derive::FromPrimitive)]
+#[repr(u8)]
+pub(crate) enum Opcodes {
+    CreateWindow = 1,
+    ChangeWindowAttributes = 2,
+    GetWindowAttributes = 3,
+    DestroyWindow = 4,
+    ReparentWindow = 7,
+    MapWindow = 8,
+    MapSubwindows = 9,
+    UnmapWindow = 10,
+    UnmapSubwindows = 11,
+    ConfigureWindow = 12,
+    CirculateWindow = 13,
+    GetGeometry = 14,
+    QueryTree = 15,
+    InternAtom = 16,
+    GetAtomName = 17,
+    ChangeProperty = 18,
+    GetProperty = 20,
+    SetSelectionOwner = 22,
+    GetSelectionOwner = 23,
+    ConvertSelection = 24,
+    SendEvent = 25,
+    GrabPointer = 26,
+    UngrabPointer = 27,
+    GrabButton = 28,
+    UngrabButton = 29,
+    GrabKeyboard = 31,
+    UngrabKeyboard = 32,
+    GrabKey = 33,
+    UngrabKey = 34,
+    AllowEvents = 35,
+    GrabServer = 36,
+    UngrabServer = 37,
+    QueryPointer = 38,
+    TranslateCoordinates = 40,
+    WarpPointer = 41,
+    SetInputFocus = 42,
+    GetInputFocus = 43,
+    QueryKeymap = 44,
+    OpenFont = 45,
+    CloseFont = 46,
+    QueryFont = 47,
+    QueryTextExtents = 48,
+    ListFonts = 49,
+    ListFontsWithInfo = 50,
+    CreatePixmap = 53,
+    FreePixmap = 54,
+    CreateGC = 55,
+    ChangeGC = 56,
+    CopyGC = 57,
+    FreeGC = 60,
+    ClearArea = 61,
+    CopyArea = 62,
+    PolyLine = 65,
+    PolySegment = 66,
+    PolyRectangle = 67,
+    PolyArc = 68,
+    PolyFillRectangle = 70,
+    PolyFillArc = 71,
+    PutImage = 72,
+    GetImage = 73,
+    ImageText8 = 76,
+    ImageText16 = 77,
+    CreateColormap = 78,
+    FreeColormap = 79,
+    AllocColor = 84,
+    QueryBestSize = 97,
+    QueryExtension = 98,
+    ListExtensions = 99,
+    GetKeyboardMapping = 101,
+    ChangeKeyboardControl = 102,
+    Bell = 104,
+    GetScreenSaver = 108,
+}
+
+#[derive(Debug)]
+pub enum ImageByteOrder {
+    LSBFirst,
+    MSBFirst,
+}
+
+#[derive(Debug)]
+pub enum BitmapFormatBitOrder {
+    LeastSignificant,
+    MostSignificant,
+}
+
+// backing stores supported by a screen, also used as value of the
+// backing-store window attribute, where `Never` is called NotUseful
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum BackingStore {
+    Never = 0,
+    WhenMapped = 1,
+    Always = 2,
+}
+
+#[derive(Debug)]
+pub enum Class {
+    StaticGray,
+    GrayScale,
+    StaticColor,
+    PseudoColor,
+    TrueColor,
+    DirectColor,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, num_derive::FromPrimitive)]
+#[repr(u8)]
+pub enum ErrorCode {
+    Request = 1,
+    Value = 2,
+    Window = 3,
+    Pixmap = 4,
+    Atom = 5,
+    Cursor = 6,
+    Font = 7,
+    Match = 8,
+    Drawable = 9,
+    Access = 10,
+    Alloc = 11,
+    Colormap = 12,
+    GContext = 13,
+    IDChoice = 14,
+    Name = 15,
+    Length = 16,
+    Implementation = 17,
+}
+
+#[bitflags]
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum Event {
+    KeyPress = 0x0000_0001,
+    KeyRelease = 0x0000_0002,
+    ButtonPress = 0x0000_0004,
+    ButtonRelease = 0x0000_0008,
+    EnterWindow = 0x0000_0010,
+    LeaveWindow = 0x0000_0020,
+    PointerMotion = 0x0000_0040,
+    PointerMotionHint = 0x0000_0080,
+    Button1Motion = 0x0000_0100,
+    Button2Motion = 0x0000_0200,
+    Button3Motion = 0x0000_0400,
+    Button4Motion = 0x0000_0800,
+    Button5Motion = 0x0000_1000,
+    ButtonMotion = 0x0000_2000,
+    KeymapState = 0x0000_4000,
+    Exposure = 0x0000_8000,
+    VisibilityChange = 0x0001_0000,
+    StructureNotify = 0x0002_0000,
+    ResizeRedirect = 0x0004_0000,
+    SubstructureNotify = 0x0008_0000,
+    SubstructureRedirect = 0x0010_0000,
+    FocusChange = 0x0020_0000,
+    PropertyChange = 0x0040_0000,
+    ColormapChange = 0x0080_0000,
+    OwnerGrabButton = 0x0100_0000,
+}
+
+// SETofDEVICEEVENT, the subset of `Event` which is allowed for the
+// do-not-propagate-mask of a window, any other bit yields a Value
+// error
+#[bitflags]
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum DeviceEvent {
+    KeyPress = 0x0000_0001,
+    KeyRelease = 0x0000_0002,
+    ButtonPress = 0x0000_0004,
+    ButtonRelease = 0x0000_0008,
+    PointerMotion = 0x0000_0040,
+    Button1Motion = 0x0000_0100,
+    Button2Motion = 0x0000_0200,
+    Button3Motion = 0x0000_0400,
+    Button4Motion = 0x0000_0800,
+    Button5Motion = 0x0000_1000,
+    ButtonMotion = 0x0000_2000,
+}
+
+pub type DeviceEventMask = BitFlags<DeviceEvent>;
+
+// SETofKEYBUTMASK, the modifier keys and pointer buttons held down, as
+// reported by input events and QueryPointer
+#[bitflags]
+#[derive(Copy, Clone, Debug)]
+#[repr(u16)]
+pub enum KeyButMask {
+    Shift = 0x0001,
+    Lock = 0x0002,
+    Control = 0x0004,
+    Mod1 = 0x0008,
+    Mod2 = 0x0010,
+    Mod3 = 0x0020,
+    Mod4 = 0x0040,
+    Mod5 = 0x0080,
+    Button1 = 0x0100,
+    Button2 = 0x0200,
+    Button3 = 0x0400,
+    Button4 = 0x0800,
+    Button5 = 0x1000,
+}
+
+// SETofPOINTEREVENT, the subset of `Event` which is allowed for the
+// event mask of GrabPointer and GrabButton, any other bit yields a
+// Value error
+pub const POINTER_EVENTS: BitFlags<Event> = make_bitflags!(Event::{
+    ButtonPress |
+    ButtonRelease |
+    EnterWindow |
+    LeaveWindow |
+    PointerMotion |
+    PointerMotionHint |
+    Button1Motion |
+    Button2Motion |
+    Button3Motion |
+    Button4Motion |
+    Button5Motion |
+    ButtonMotion |
+    KeymapState});
+
+// Events only one client at a time may select on a window. Selecting
+// one of them while another client has it selected yields an Access
+// error, e.g. SubstructureRedirect on the root window if a window
+// manager is running already.
+pub const EXCLUSIVE_EVENTS: BitFlags<Event> =
+    make_bitflags!(Event::{SubstructureRedirect | ResizeRedirect | ButtonPress});
+
+// Selecting `events`, the exclusive events of the mask, on `window`
+// failed. The error doesn't tell which of them another client has
+// selected, so all of them are reported.
+#[derive(Debug)]
+pub struct EventMaskConflict {
+    pub window: WindowId,
+    pub events: BitFlags<Event>,
+}
+
+impl fmt::Display for EventMaskConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "selecting {} on window {} failed, probably another client has selected them, \
+             only one client at a time may",
+            self.events, self.window
+        )
+    }
+}
+
+impl error::Error for EventMaskConflict {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, num_derive::FromPrimitive)]
+#[repr(u8)]
+pub enum Events {
+    KeyPress = 2,
+    KeyRelease = 3,
+    ButtonPress = 4,
+    ButtonRelease = 5,
+    MotionNotify = 6,
+    EnterNotify = 7,
+    LeaveNotify = 8,
+    FocusIn = 9,
+    FocusOut = 10,
+    KeymapNotify = 11,
+    Expose = 12,
+    GraphicsExposure = 13,
+    NoExposure = 14,
+    VisibilityNotify = 15,
+    CreateNotify = 16,
+    DestroyNotify = 17,
+    UnmapNotify = 18,
+    MapNotify = 19,
+    MapRequest = 20,
+    ReparentNotify = 21,
+    ConfigureNotify = 22,
+    // ...
+    PropertyNotify = 28,
+    SelectionClear = 29,
+    SelectionRequest = 30,
+    SelectionNotify = 31,
+    ColormapNotify = 32,
+    ClientMessage = 33,
+    MappingNotify = 34,
+}
+
+impl Events {
+    // kind of a raw 32 byte event like `DecodedEvent::Other`, `None`
+    // for extension events
+    #[must_use]
+    pub fn of(event: &[u8]) -> Option<Self> {
+        event.first().and_then(|code| Self::from_u8(code & 0x7f))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, num_derive::FromPrimitive)]
+#[repr(u8)]
+pub enum MappingNotifyRequest {
+    Modifier = 0,
+    Keyboard = 1,
+    Pointer = 2,
+}
+
+#[bitflags]
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum CreateGcBits {
+    Function = 0x1,
+    PlaneMask = 0x2,
+    Foreground = 0x4,
+    Background = 0x8,
+    LineWidth = 0x10,
+    LineStyle = 0x20,
+    CapStyle = 0x40,
+    JoinStyle = 0x80,
+    FillStyle = 0x100,
+    FillRule = 0x200,
+    Tile = 0x400,
+    Stipple = 0x800,
+    TileStippleXOrigin = 0x1000,
+    TileStippleYOrigin = 0x2000,
+    Font = 0x4000,
+    SubwindowMode = 0x8000,
+    GraphicsExposures = 0x10000,
+    ClipXOrigin = 0x20000,
+    ClipYOrigin = 0x40000,
+    ClipMask = 0x80000,
+    DashOffset = 0x0010_0000,
+    Dashes = 0x0020_0000,
+    ArcMode = 0x0040_0000,
+}
+
+#[bitflags]
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum WindowAttributeBits {
+    BackgroundPixmap = 0x0000_0001,
+    BackgroundPixel = 0x0000_0002,
+    BorderPixmap = 0x0000_0004,
+    BorderPixel = 0x0000_0008,
+    BitGravity = 0x0000_0010,
+    WinGravity = 0x0000_0020,
+    BackingStore = 0x0000_0040,
+    BackingPlanes = 0x0000_0080,
+    BackingPixel = 0x0000_0100,
+    OverrideRedirect = 0x0000_0200,
+    SaveUnder = 0x0000_0400,
+    EventMask = 0x0000_0800,
+    DoNotPropagateMask = 0x0000_1000,
+    Colormap = 0x0000_2000,
+    Cursor = 0x0000_4000,
+}
+
+// a window attribute with its value, see `change_window_attributes`
+#[derive(Copy, Clone, Debug)]
+pub enum WindowAttribute {
+    // 0 is None, 1 ParentRelative
+    BackgroundPixmap(PixmapId),
+    BackgroundPixel(u32),
+    // 0 is CopyFromParent
+    BorderPixmap(PixmapId),
+    BorderPixel(u32),
+    BitGravity(u8),
+    WinGravity(u8),
+    BackingStore(BackingStore),
+    BackingPlanes(u32),
+    BackingPixel(u32),
+    OverrideRedirect(bool),
+    SaveUnder(bool),
+    EventMask(BitFlags<Event>),
+    DoNotPropagateMask(DeviceEventMask),
+    // 0 is CopyFromParent
+    Colormap(ColorMap),
+    // 0 is None
+    Cursor(u32),
+}
+
+impl WindowAttribute {
+    // bit of the attribute in the value-mask and its value
+    fn bit_and_value(self) -> (WindowAttributeBits, u32) {
+        match self {
+            Self::BackgroundPixmap(pixmap) => (WindowAttributeBits::BackgroundPixmap, pixmap),
+            Self::BackgroundPixel(pixel) => (WindowAttributeBits::BackgroundPixel, pixel),
+            Self::BorderPixmap(pixmap) => (WindowAttributeBits::BorderPixmap, pixmap),
+            Self::BorderPixel(pixel) => (WindowAttributeBits::BorderPixel, pixel),
+            Self::BitGravity(gravity) => (WindowAttributeBits::BitGravity, gravity.into()),
+            Self::WinGravity(gravity) => (WindowAttributeBits::WinGravity, gravity.into()),
+            Self::BackingStore(backing_store) => {
+                (WindowAttributeBits::BackingStore, backing_store as u32)
+            }
+            Self::BackingPlanes(planes) => (WindowAttributeBits::BackingPlanes, planes),
+            Self::BackingPixel(pixel) => (WindowAttributeBits::BackingPixel, pixel),
+            Self::OverrideRedirect(override_redirect) => (
+                WindowAttributeBits::OverrideRedirect,
+                override_redirect.into(),
+            ),
+            Self::SaveUnder(save_under) => (WindowAttributeBits::SaveUnder, save_under.into()),
+            Self::EventMask(event_mask) => (WindowAttributeBits::EventMask, event_mask.bits()),
+            Self::DoNotPropagateMask(do_not_propagate_mask) => (
+                WindowAttributeBits::DoNotPropagateMask,
+                do_not_propagate_mask.bits(),
+            ),
+            Self::Colormap(colormap) => (WindowAttributeBits::Colormap, colormap),
+            Self::Cursor(cursor) => (WindowAttributeBits::Cursor, cursor),
+        }
+    }
+}
+
+// Collects the values of a value-list by their bit. Values must be
+// sent in the order of their bits, a value given more than once
+// overrides the previous one.
+fn value_list(values: impl IntoIterator<Item = (u32, u32)>) -> BTreeMap<u32, u32> {
+    values.into_iter().collect()
+}
+
+// writes the bitmask followed by the values of a value-list
+fn put_value_list(buf: &mut impl OrderedBufMut, values: &BTreeMap<u32, u32>) {
+    buf.put_card32(values.keys().fold(0, |bitmask, bit| bitmask | bit)); // bitmask
+
+    // list-of-values
+    for value in values.values() {
+        buf.put_card32(*value);
+    }
+}
+
+pub type WindowId = u32;
+pub type GCId = u32;
+pub type ColorMap = u32;
+pub type PixmapId = u32;
+pub type VisualId = u32;
+pub type Atom = u32;
+
+// predefined atoms, which don't need to be interned
+pub(crate) const ATOM_CARDINAL: Atom = 6;
+pub(crate) const ATOM_STRING: Atom = 31;
+pub(crate) const ATOM_WM_NAME: Atom = 39;
+pub(crate) const ATOM_WM_CLASS: Atom = 67;
+
+// failure to set up a connection
+#[derive(Debug)]
+pub enum ConnectionError {
+    // the server refused the connection, e.g. for an unsupported
+    // protocol version or missing authorization
+    SetupFailed { reason: String },
+    // the server wants further authentication, which isn't supported
+    AuthRequired { reason: String },
+    // the setup reply has a status which the protocol doesn't define
+    UnknownStatus(u8),
+    Protocol(DecodeError),
+    Io(io::Error),
+}
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SetupFailed { reason } => write!(f, "connection refused: {reason}"),
+            Self::AuthRequired { reason } => write!(f, "authentication required: {reason}"),
+            Self::UnknownStatus(status) => write!(f, "unknown connection setup status {status}"),
+            Self::Protocol(decode_error) => write!(f, "invalid connection setup: {decode_error}"),
+            Self::Io(io_error) => write!(f, "{io_error}"),
+        }
+    }
+}
+
+impl error::Error for ConnectionError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Protocol(decode_error) => Some(decode_error),
+            Self::Io(io_error) => Some(io_error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ConnectionError {
+    fn from(io_error: io::Error) -> Self {
+        Self::Io(io_error)
+    }
+}
+
+impl From<LengthOverflow> for ConnectionError {
+    fn from(length_overflow: LengthOverflow) -> Self {
+        Self::Io(length_overflow.into())
+    }
+}
+
+impl From<DecodeError> for ConnectionError {
+    fn from(decode_error: DecodeError) -> Self {
+        Self::Protocol(decode_error)
+    }
+}
+
+#[derive(Debug)]
+pub struct Format {
+    pub depth: u8,
+    pub bits_per_pixel: u8,
+    pub scanline_pad: u8,
+}
+
+#[derive(Debug)]
+pub struct Setup {
+    pub release_number: u32,
+    pub resource_id_base: u32,
+    pub resource_id_mask: u32,
+    pub motion_buffer_size: u32,
+    pub vendor: AsciiString,
+    pub maximum_request_length: u16,
+    pub image_byte_order: ImageByteOrder,
+    pub bitmap_format_bit_order: BitmapFormatBitOrder,
+    pub bitmap_format_scanline_unit: u8,
+    pub bitmap_format_scanline_pad: u8,
+    pub min_keycode: u8,
+    pub max_keycode: u8,
+    pub formats: Vec<Format>,
+    pub screens: Vec<Screen>,
+}
+
+impl Setup {
+    // decodes the additional data of a successful connection setup
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "setup")?;
+        let release_number = buf.get_card32();
+        let resource_id_base = buf.get_card32();
+        let resource_id_mask = buf.get_card32();
+        let motion_buffer_size = buf.get_card32();
+        let vendor_len = buf.get_card16() as usize;
+        let maximum_request_length = buf.get_card16();
+        let number_screens_roots = buf.get_u8() as usize;
+        let number_formats = buf.get_u8() as usize;
+
+        log::debug!(
+            "number of screens: {number_screens_roots}, number of formats: {number_formats}"
+        );
+
+        let image_byte_order = match buf.get_u8() {
+            0 => ImageByteOrder::LSBFirst,
+            1 => ImageByteOrder::MSBFirst,
+            x => return Err(DecodeError::InvalidValue("image byte order", x.into())),
+        };
+
+        let bitmap_format_bit_order = match buf.get_u8() {
+            0 => BitmapFormatBitOrder::LeastSignificant,
+            1 => BitmapFormatBitOrder::MostSignificant,
+            x => {
+                return Err(DecodeError::InvalidValue(
+                    "bitmap format bit order",
+                    x.into(),
+                ))
+            }
+        };
+
+        let bitmap_format_scanline_unit = buf.get_u8();
+        let bitmap_format_scanline_pad = buf.get_u8();
+
+        let min_keycode = buf.get_u8();
+        let max_keycode = buf.get_u8();
+
+        buf.advance(4);
+
+        ensure_remaining(buf, vendor_len + pad(vendor_len), "vendor")?;
+        let vendor = get_ascii(buf, vendor_len, "vendor")?;
+        log::debug!("vendor: {vendor}");
+        buf.advance(pad(vendor_len));
+
+        let mut formats: Vec<Format> = Vec::new();
+        for _current_format in 0..number_formats {
+            ensure_remaining(buf, 8, "format")?;
+            formats.push(Format {
+                depth: buf.get_u8(),
+                bits_per_pixel: buf.get_u8(),
+                scanline_pad: buf.get_u8(),
+            });
+            buf.advance(5);
+        }
+
+        let mut screen_roots: Vec<Screen> = Vec::with_capacity(number_screens_roots);
+        for _screen in 0..number_screens_roots {
+            screen_roots.push(Screen::from_bytes(buf)?);
+        }
+
+        Ok(Self {
+            release_number,
+            resource_id_base,
+            resource_id_mask,
+            motion_buffer_size,
+            vendor,
+            maximum_request_length,
+            image_byte_order,
+            bitmap_format_bit_order,
+            bitmap_format_scanline_unit,
+            bitmap_format_scanline_pad,
+            min_keycode,
+            max_keycode,
+            formats,
+            screens: screen_roots,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Screen {
+    pub window: WindowId,
+    pub default_colormap: ColorMap,
+    pub white_pixel: u32,
+    pub black_pixel: u32,
+    pub current_input_masks: BitFlags<Event>,
+    pub width_pixels: u16,  // in pixels
+    pub height_pixels: u16, // in pixels
+    pub width_mm: u16,      // in millimeters
+    pub height_mm: u16,     // in millimeters
+    pub min_installed_maps: u16,
+    pub max_installed_maps: u16,
+    pub root_visual: VisualId,
+    pub backing_stores: BackingStore,
+    pub save_unders: bool,
+    pub root_depth: u8,
+    pub number_depths_in_allowed_depths: u8,
+    pub allowed_depths: Vec<Depth>,
+}
+
+// the root window of a screen along with its depth, visual and
+// colormap, which a top-level window needs to match
+#[derive(Copy, Clone, Debug)]
+pub struct RootInfo {
+    pub window: WindowId,
+    pub depth: u8,
+    pub visual: VisualId,
+    pub colormap: ColorMap,
+}
+
+impl Screen {
+    #[must_use]
+    pub fn root(&self) -> RootInfo {
+        RootInfo {
+            window: self.window,
+            depth: self.root_depth,
+            visual: self.root_visual,
+            colormap: self.default_colormap,
+        }
+    }
+
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 40, "screen")?;
+        let mut screen = Self {
+            window: buf.get_card32(),
+            default_colormap: buf.get_card32(),
+            white_pixel: buf.get_card32(),
+            black_pixel: buf.get_card32(),
+            current_input_masks: {
+                let masks = buf.get_card32();
+                BitFlags::from_bits(masks)
+                    .map_err(|_| DecodeError::InvalidValue("current input masks", masks))?
+            },
+            width_pixels: buf.get_card16(),
+            height_pixels: buf.get_card16(),
+            width_mm: buf.get_card16(),
+            height_mm: buf.get_card16(),
+            min_installed_maps: buf.get_card16(),
+            max_installed_maps: buf.get_card16(),
+            root_visual: buf.get_card32(),
+            backing_stores: match buf.get_u8() {
+                0 => BackingStore::Never,
+                1 => BackingStore::WhenMapped,
+                2 => BackingStore::Always,
+                other => return Err(DecodeError::InvalidValue("backing stores", other.into())),
+            },
+            save_unders: match buf.get_u8() {
+                0 => false,
+                1 => true,
+                other => return Err(DecodeError::InvalidValue("save unders", other.into())),
+            },
+            root_depth: buf.get_u8(),
+            number_depths_in_allowed_depths: buf.get_u8(),
+            allowed_depths: Vec::new(),
+        };
+        for _allowed_depth in 0..(screen.number_depths_in_allowed_depths) {
+            screen.allowed_depths.push(Depth::from_bytes(buf)?);
+        }
+
+        Ok(screen)
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::struct_field_names)]
+pub struct Depth {
+    pub depth: u8,
+    pub number_visual_types: u16,
+    pub visuals: Vec<VisualType>,
+}
+
+impl Depth {
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 8, "depth")?;
+        let mut depth = Self {
+            depth: {
+                let depth = buf.get_u8();
+                buf.advance(1);
+                depth
+            },
+            number_visual_types: {
+                let number_of_visual_types = buf.get_card16();
+                buf.advance(4);
+                number_of_visual_types
+            },
+            visuals: Vec::new(),
+        };
+        for _visual in 0..(depth.number_visual_types) {
+            depth.visuals.push(VisualType::from_bytes(buf)?);
+        }
+
+        Ok(depth)
+    }
+}
+
+#[derive(Debug)]
+pub struct VisualType {
+    pub visual_id: VisualId,
+    pub class: Class,
+    pub bits_per_rgb_value: u8,
+    pub colormap_entries: u16,
+    pub red_mask: u32,
+    pub green_mask: u32,
+    pub blue_mask: u32,
+}
+
+impl VisualType {
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 24, "visual type")?;
+        let visual_type = Self {
+            visual_id: buf.get_card32(),
+            class: match buf.get_u8() {
+                0 => Class::StaticGray,
+                1 => Class::GrayScale,
+                2 => Class::StaticColor,
+                3 => Class::PseudoColor,
+                4 => Class::TrueColor,
+                5 => Class::DirectColor,
+                other => return Err(DecodeError::InvalidValue("visual class", other.into())),
+            },
+            bits_per_rgb_value: buf.get_u8(),
+            colormap_entries: buf.get_card16(),
+            red_mask: buf.get_card32(),
+            green_mask: buf.get_card32(),
+            blue_mask: buf.get_card32(),
+        };
+        buf.advance(4); // unused
+
+        Ok(visual_type)
+    }
+}
+
+// events selected on the windows created by `Connection`
+pub(crate) const DEFAULT_EVENT_MASK: BitFlags<Event> = make_bitflags!(Event::{
+    KeyPress |
+    KeyRelease |
+    ButtonPress |
+    ButtonRelease |
+    EnterWindow |
+    LeaveWindow |
+    Exposure});
+
+// The background is white, unless `values` sets the background. The
+// event mask can also be overridden by `values`. A `depth` and
+// `visual` of 0 are copied from the parent.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_window_request(
+    buf: &mut impl OrderedBufMut,
+    screen: &Screen,
+    parent: WindowId,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    border_width: u16,
+    depth: u8,
+    class: u16,
+    visual: VisualId,
+    event_mask: BitFlags<Event>,
+    values: &[(WindowAttributeBits, u32)],
+    id_generator: &mut impl Iterator<Item = u32>,
+) -> io::Result<WindowId> {
+    let values = value_list(
+        [
+            (WindowAttributeBits::BackgroundPixel, screen.white_pixel),
+            (WindowAttributeBits::EventMask, event_mask.bits()),
+        ]
+        .iter()
+        .chain(values)
+        .map(|&(bit, value)| (bit as u32, value)),
+    );
+    let request_length = request_len_words(32, 4 * values.len())?;
+    let id = next_id(id_generator)?;
+
+    buf.put_u8(Opcodes::CreateWindow as u8); // opcode
+    buf.put_u8(depth); // depth, 0 means copy from parent
+    buf.put_card16(request_length); // request len
+    buf.put_card32(id); // wid
+    buf.put_card32(parent); // parent
+    buf.put_int16(x); // x
+    buf.put_int16(y); // y
+    buf.put_card16(width); // width
+    buf.put_card16(height); // height
+    buf.put_card16(border_width); // border-width
+    buf.put_card16(class); // class, 0 CopyFromParent, 1 InputOutput, 2 InputOnly
+    buf.put_card32(visual); // visual id, 0 means copy from parent
+    put_value_list(buf, &values);
+
+    Ok(id)
+}
+
+pub(crate) fn destroy_window_request(buf: &mut impl OrderedBufMut, wid: WindowId) {
+    buf.put_u8(Opcodes::DestroyWindow as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(wid); // wid
+}
+
+// moves `window_id` into `parent` at (`x`, `y`), relative to the
+// origin of `parent`, a mapped window is unmapped and mapped again
+pub(crate) fn reparent_window(
+    buf: &mut impl OrderedBufMut,
+    window_id: WindowId,
+    parent: WindowId,
+    x: i16,
+    y: i16,
+) {
+    buf.put_u8(Opcodes::ReparentWindow as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(4); // request length
+    buf.put_card32(window_id); // window
+    buf.put_card32(parent); // parent
+    buf.put_int16(x); // x
+    buf.put_int16(y); // y
+}
+
+// Unlike the value-list of other requests, an attribute given more
+// than once is rejected, since it's ambiguous which value is meant.
+pub(crate) fn change_window_attributes(
+    buf: &mut impl OrderedBufMut,
+    window_id: WindowId,
+    values: &[WindowAttribute],
+) -> io::Result<()> {
+    let mut value_list = BTreeMap::new();
+    for value in values {
+        let (bit, value) = value.bit_and_value();
+        if value_list.insert(bit as u32, value).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("window attribute {bit:?} given more than once"),
+            ));
+        }
+    }
+    let request_length = request_len_words(12, 4 * value_list.len())?;
+
+    buf.put_u8(Opcodes::ChangeWindowAttributes as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(request_length); // request length
+    buf.put_card32(window_id); // window
+    put_value_list(buf, &value_list);
+
+    Ok(())
+}
+
+// sets the event mask of the window via ChangeWindowAttributes
+pub(crate) fn select_input(
+    buf: &mut impl OrderedBufMut,
+    window_id: WindowId,
+    event_mask: BitFlags<Event>,
+) {
+    buf.put_u8(Opcodes::ChangeWindowAttributes as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(4); // request length
+    buf.put_card32(window_id); // window
+    buf.put_card32(WindowAttributeBits::EventMask as u32); // value-mask
+    buf.put_card32(event_mask.bits()); // event-mask
+}
+
+// sets the do-not-propagate-mask of the window via
+// ChangeWindowAttributes
+pub(crate) fn set_do_not_propagate_mask(
+    buf: &mut impl OrderedBufMut,
+    window_id: WindowId,
+    do_not_propagate_mask: DeviceEventMask,
+) {
+    buf.put_u8(Opcodes::ChangeWindowAttributes as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(4); // request length
+    buf.put_card32(window_id); // window
+    buf.put_card32(WindowAttributeBits::DoNotPropagateMask as u32); // value-mask
+    buf.put_card32(do_not_propagate_mask.bits()); // do-not-propagate-mask
+}
+
+pub(crate) fn get_window_attributes_request(buf: &mut impl OrderedBufMut, wid: WindowId) {
+    buf.put_u8(Opcodes::GetWindowAttributes as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(wid); // wid
+}
+
+#[derive(Debug)]
+pub struct WindowAttributesReply {
+    pub backing_store: u8,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub visual: VisualId,
+    // 1 is InputOutput, 2 InputOnly
+    pub class: u16,
+    pub bit_gravity: u8,
+    pub win_gravity: u8,
+    pub backing_planes: u32,
+    pub backing_pixel: u32,
+    pub save_under: bool,
+    pub map_is_installed: bool,
+    pub map_state: MapState,
+    pub override_redirect: bool,
+    // 0 is None
+    pub colormap: ColorMap,
+    // events selected by all clients
+    pub all_event_masks: BitFlags<Event>,
+    // events selected by this client
+    pub your_event_mask: BitFlags<Event>,
+    pub do_not_propagate_mask: DeviceEventMask,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapState {
+    Unmapped,
+    // mapped, but an ancestor is unmapped
+    Unviewable,
+    Viewable,
+}
+
+impl WindowAttributesReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 44, "GetWindowAttributes reply")?;
+        buf.advance(1); // reply
+        let this = Self {
+            backing_store: buf.get_u8(),
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            visual: buf.get_card32(),
+            class: buf.get_card16(),
+            bit_gravity: buf.get_u8(),
+            win_gravity: buf.get_u8(),
+            backing_planes: buf.get_card32(),
+            backing_pixel: buf.get_card32(),
+            save_under: buf.get_u8() != 0,
+            map_is_installed: buf.get_u8() != 0,
+            map_state: match buf.get_u8() {
+                0 => MapState::Unmapped,
+                1 => MapState::Unviewable,
+                2 => MapState::Viewable,
+                other => return Err(DecodeError::InvalidValue("map-state", other.into())),
+            },
+            override_redirect: buf.get_u8() != 0,
+            colormap: buf.get_card32(),
+            all_event_masks: BitFlags::from_bits_truncate(buf.get_card32()),
+            your_event_mask: BitFlags::from_bits_truncate(buf.get_card32()),
+            do_not_propagate_mask: BitFlags::from_bits_truncate(buf.get_card16().into()),
+        };
+        buf.advance(2); // unused
+
+        Ok(this)
+    }
+}
+
+pub(crate) fn get_geometry(buf: &mut impl OrderedBufMut, drawable: u32) {
+    buf.put_u8(Opcodes::GetGeometry as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(drawable); // drawable
+}
+
+#[derive(Debug)]
+pub struct GetGeometryReply {
+    pub depth: u8,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub root: WindowId,
+    // relative to the parent's origin, the outer upper left corner
+    // including the border
+    pub x: i16,
+    pub y: i16,
+    // inside size, excluding the border
+    pub width: u16,
+    pub height: u16,
+    pub border_width: u16,
+}
+
+impl GetGeometryReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // reply
+        let this = Self {
+            depth: buf.get_u8(),
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            root: buf.get_card32(),
+            x: buf.get_int16(),
+            y: buf.get_int16(),
+            width: buf.get_card16(),
+            height: buf.get_card16(),
+            border_width: buf.get_card16(),
+        };
+        buf.advance(10); // unused
+
+        this
+    }
+}
+
+pub(crate) fn query_tree(buf: &mut impl OrderedBufMut, window_id: WindowId) {
+    buf.put_u8(Opcodes::QueryTree as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(window_id); // window
+}
+
+#[derive(Debug)]
+pub struct QueryTreeReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub root: WindowId,
+    // `None` for a root window
+    pub parent: Option<WindowId>,
+    // in stacking order, bottom-most first
+    pub children: Vec<WindowId>,
+}
+
+impl QueryTreeReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "QueryTree reply")?;
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let root = buf.get_card32();
+        let parent = Some(buf.get_card32()).filter(|&parent| parent != 0);
+        let number_of_children = buf.get_card16();
+        buf.advance(14); // unused
+        ensure_remaining(buf, usize::from(number_of_children) * 4, "children")?;
+        let children = (0..number_of_children)
+            .map(|_child| buf.get_card32())
+            .collect();
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            root,
+            parent,
+            children,
+        })
+    }
+}
+
+// translates (`src_x`, `src_y`) relative to `src_window` to
+// coordinates relative to `dst_window`
+pub(crate) fn translate_coordinates(
+    buf: &mut impl OrderedBufMut,
+    src_window: WindowId,
+    dst_window: WindowId,
+    src_x: i16,
+    src_y: i16,
+) {
+    buf.put_u8(Opcodes::TranslateCoordinates as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(4); // request length
+    buf.put_card32(src_window); // src-window
+    buf.put_card32(dst_window); // dst-window
+    buf.put_int16(src_x); // src-x
+    buf.put_int16(src_y); // src-y
+}
+
+#[derive(Debug)]
+pub struct TranslateCoordinatesReply {
+    // if false, the windows are on different screens and the
+    // coordinates are 0
+    pub same_screen: bool,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // child of the destination window containing the point, 0 is None
+    pub child: WindowId,
+    pub dst_x: i16,
+    pub dst_y: i16,
+}
+
+impl TranslateCoordinatesReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // reply
+        let this = Self {
+            same_screen: buf.get_u8() != 0,
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            child: buf.get_card32(),
+            dst_x: buf.get_int16(),
+            dst_y: buf.get_int16(),
+        };
+        buf.advance(16); // unused
+
+        this
+    }
+}
+
+pub(crate) fn intern_atom(
+    buf: &mut impl OrderedBufMut,
+    only_if_exists: bool,
+    name: &[u8],
+) -> Result<(), LengthOverflow> {
+    let n = name.len();
+    let request_length = request_len_words(8, n)?;
+    let name_length: u16 = n.try_into()?;
+    buf.put_u8(Opcodes::InternAtom as u8); // opcode
+    buf.put_u8(only_if_exists.into()); // only-if-exists
+    buf.put_card16(request_length); // request length
+    buf.put_card16(name_length); // length of name
+    buf.put_card16(0); // unused
+    buf.put_slice(name);
+    buf.put_bytes(0, pad(n));
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct InternAtomReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // 0 (None) if only-if-exists was given and the atom doesn't exist
+    pub atom: Atom,
+}
+
+impl InternAtomReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            atom: buf.get_card32(),
+        };
+        buf.advance(20); // unused
+
+        this
+    }
+}
+
+pub(crate) fn get_atom_name(buf: &mut impl OrderedBufMut, atom: Atom) {
+    buf.put_u8(Opcodes::GetAtomName as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(atom); // atom
+}
+
+#[derive(Debug)]
+pub struct GetAtomNameReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub name: String,
+}
+
+impl GetAtomNameReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "GetAtomName reply")?;
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let name_length = usize::from(buf.get_card16());
+        buf.advance(22); // unused
+        ensure_remaining(buf, name_length + pad(name_length), "name")?;
+        // the name is a STRING8, whose bytes are Latin-1, the first 256
+        // code points of Unicode
+        let name = buf
+            .copy_to_bytes(name_length)
+            .iter()
+            .map(|&byte| char::from(byte))
+            .collect();
+        buf.advance(pad(name_length));
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            name,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum PropertyMode {
+    Replace = 0,
+    Prepend = 1,
+    Append = 2,
+}
+
+// `data` holds the values of the property in units of `format` bits,
+// which is 8, 16 or 32, in the byte order of the connection
+pub(crate) fn change_property(
+    buf: &mut impl OrderedBufMut,
+    mode: PropertyMode,
+    window_id: WindowId,
+    property: Atom,
+    type_: Atom,
+    format: u8,
+    data: &[u8],
+) -> io::Result<()> {
+    if !matches!(format, 8 | 16 | 32) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("format {format} is none of 8, 16 or 32"),
+        ));
+    }
+    let unit = usize::from(format / 8);
+    if !data.len().is_multiple_of(unit) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} bytes of data aren't a multiple of format {format}",
+                data.len()
+            ),
+        ));
+    }
+    let request_len = big_request_len_words(24, data.len())?;
+    let data_len = u32::try_from(data.len() / unit).map_err(LengthOverflow::from)?;
+
+    buf.put_u8(Opcodes::ChangeProperty as u8); // opcode
+    buf.put_u8(mode as u8); // mode
+    put_request_length(buf, request_len);
+    buf.put_card32(window_id); // window
+    buf.put_card32(property); // property
+    buf.put_card32(type_); // type
+    buf.put_u8(format); // format
+    buf.put_bytes(0, 3); // unused
+    buf.put_card32(data_len); // length of data in format units
+    buf.put_slice(data);
+    buf.put_bytes(0, pad(data.len())); // padding
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn get_property(
+    buf: &mut impl OrderedBufMut,
+    delete: bool,
+    window_id: WindowId,
+    property: Atom,
+    type_: Atom,
+    long_offset: u32,
+    long_length: u32,
+) {
+    buf.put_u8(Opcodes::GetProperty as u8); // opcode
+    buf.put_u8(delete.into()); // delete
+    buf.put_card16(6); // request length
+    buf.put_card32(window_id); // window
+    buf.put_card32(property); // property
+    buf.put_card32(type_); // type, 0 is AnyPropertyType
+    buf.put_card32(long_offset); // long-offset
+    buf.put_card32(long_length); // long-length
+}
+
+// long-length of the GetProperty requests of
+// `Connection::get_property_full`, i.e. 64 KiB each
+pub(crate) const PROPERTY_CHUNK_LEN: u32 = 0x4000;
+
+#[derive(Debug)]
+pub struct GetPropertyReply {
+    // 0 if the property doesn't exist
+    pub format: u8,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub type_: Atom,
+    pub bytes_after: u32,
+    // in units of the format
+    pub value_len: u32,
+    pub value: Bytes,
+}
+
+impl GetPropertyReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "GetProperty reply")?;
+        buf.advance(1); // reply
+        let format = buf.get_u8();
+        // 0 if the property doesn't exist
+        if !matches!(format, 0 | 8 | 16 | 32) {
+            return Err(DecodeError::InvalidValue("format", format.into()));
+        }
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let type_ = buf.get_card32();
+        let bytes_after = buf.get_card32();
+        let value_len = buf.get_card32();
+        buf.advance(12); // unused
+        let n = (value_len as usize).saturating_mul(usize::from(format / 8));
+        ensure_remaining(buf, n.saturating_add(pad(n)), "value")?;
+        let value = buf.copy_to_bytes(n);
+        buf.advance(pad(n));
+
+        Ok(Self {
+            format,
+            sequence_number,
+            reply_length,
+            type_,
+            bytes_after,
+            value_len,
+            value,
+        })
+    }
+}
+
+// one of the icons of _NET_WM_ICON
+#[derive(Debug)]
+pub struct IconImage {
+    pub width: u32,
+    pub height: u32,
+    // row by row, 0xAARRGGBB each, the color isn't premultiplied by
+    // the alpha
+    pub pixels: Vec<u32>,
+}
+
+impl IconImage {
+    // The value of _NET_WM_ICON is an array of CARDINALs holding the
+    // icons one after the other, each its width, its height, then its
+    // pixels. A truncated last icon is dropped.
+    pub(crate) fn from_property(mut buf: impl OrderedBuf) -> Vec<Self> {
+        let mut icons = Vec::new();
+        while buf.remaining() >= 8 {
+            let width = buf.get_card32();
+            let height = buf.get_card32();
+            let Some(len) = usize::try_from(width)
+                .ok()
+                .zip(usize::try_from(height).ok())
+                .and_then(|(width, height)| width.checked_mul(height))
+                .filter(|&len| len <= buf.remaining() / 4)
+            else {
+                break;
+            };
+            let pixels = (0..len).map(|_pixel| buf.get_card32()).collect();
+            icons.push(Self {
+                width,
+                height,
+                pixels,
+            });
+        }
+
+        icons
+    }
+}
+
+// makes `owner` the owner of `selection`, 0 (None) gives it up
+pub(crate) fn set_selection_owner(
+    buf: &mut impl OrderedBufMut,
+    owner: WindowId,
+    selection: Atom,
+    time: u32,
+) {
+    buf.put_u8(Opcodes::SetSelectionOwner as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(4); // request length
+    buf.put_card32(owner); // owner, 0 is None
+    buf.put_card32(selection); // selection
+    buf.put_card32(time); // time, 0 is CurrentTime
+}
+
+pub(crate) fn get_selection_owner(buf: &mut impl OrderedBufMut, selection: Atom) {
+    buf.put_u8(Opcodes::GetSelectionOwner as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(2); // request length
+    buf.put_card32(selection); // selection
+}
+
+#[derive(Debug)]
+pub struct GetSelectionOwnerReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // `None` if nobody owns the selection
+    pub owner: Option<WindowId>,
+}
+
+impl GetSelectionOwnerReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            owner: Some(buf.get_card32()).filter(|&owner| owner != 0),
+        };
+        buf.advance(20); // unused
+
+        this
+    }
+}
+
+pub(crate) fn convert_selection(
+    buf: &mut impl OrderedBufMut,
+    requestor: WindowId,
+    selection: Atom,
+    target: Atom,
+    property: Atom,
+    time: u32,
+) {
+    buf.put_u8(Opcodes::ConvertSelection as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(6); // request length
+    buf.put_card32(requestor); // requestor
+    buf.put_card32(selection); // selection
+    buf.put_card32(target); // target
+    buf.put_card32(property); // property, 0 is None
+    buf.put_card32(time); // time, 0 is CurrentTime
+}
+
+// destinations of SendEvent besides a window
+pub const POINTER_WINDOW: WindowId = 0;
+pub const INPUT_FOCUS: WindowId = 1;
+
+// sends `event`, which must be a complete 32 bytes event, to the
+// clients selecting `event_mask` on `destination`
+pub(crate) fn send_event(
+    buf: &mut impl OrderedBufMut,
+    propagate: bool,
+    destination: WindowId,
+    event_mask: BitFlags<Event>,
+    event: &[u8; 32],
+) {
+    buf.put_u8(Opcodes::SendEvent as u8); // opcode
+    buf.put_u8(propagate.into()); // propagate
+    buf.put_card16(11); // request length
+    buf.put_card32(destination); // destination, 0 is PointerWindow, 1 InputFocus
+    buf.put_card32(event_mask.bits()); // event-mask
+    buf.put_slice(event);
+}
+
+// A ClientMessage event for SendEvent, the format follows from the
+// data. `byte_order` is the one of the connection, see
+// `Connection::byte_order`.
+#[must_use]
+pub fn encode_client_message(
+    byte_order: ByteOrder,
+    window: WindowId,
+    type_: Atom,
+    data: &ClientMessageData,
+) -> [u8; 32] {
+    let format = match data {
+        ClientMessageData::Data8(_) => 8,
+        ClientMessageData::Data16(_) => 16,
+        ClientMessageData::Data32(_) => 32,
+    };
+    let mut event = [0; 32];
+    let mut buf = Ordered::new(&mut event[..], byte_order);
+    buf.put_u8(Events::ClientMessage as u8); // code
+    buf.put_u8(format); // format
+    buf.put_card16(0); // sequence number, set by the server
+    buf.put_card32(window); // window
+    buf.put_card32(type_); // type
+    match data {
+        ClientMessageData::Data8(data) => buf.put_slice(data),
+        ClientMessageData::Data16(data) => {
+            for &value in data {
+                buf.put_card16(value);
+            }
+        }
+        ClientMessageData::Data32(data) => {
+            for &value in data {
+                buf.put_card32(value);
+            }
+        }
+    }
+
+    event
+}
+
+// KeyPress or KeyRelease of `keycode` in `window`, as if the pointer
+// was at (1, 1) of the window
+pub(crate) fn key_event(
+    byte_order: ByteOrder,
+    press: bool,
+    keycode: u8,
+    root: WindowId,
+    window: WindowId,
+    state: u16,
+) -> [u8; 32] {
+    let code = if press {
+        Events::KeyPress
+    } else {
+        Events::KeyRelease
+    };
+    let mut event = [0; 32];
+    let mut buf = Ordered::new(&mut event[..], byte_order);
+    // the server sets the bit marking events sent by SendEvent anyway
+    buf.put_u8(code as u8 | 0x80); // code
+    buf.put_u8(keycode); // detail
+    buf.put_card16(0); // sequence number, set by the server
+    buf.put_card32(0); // time, 0 is CurrentTime
+    buf.put_card32(root); // root
+    buf.put_card32(window); // event
+    buf.put_card32(0); // child, 0 is None
+    buf.put_int16(1); // root-x
+    buf.put_int16(1); // root-y
+    buf.put_int16(1); // event-x
+    buf.put_int16(1); // event-y
+    buf.put_card16(state); // state, SETofKEYBUTMASK
+    buf.put_u8(1); // same-screen
+    buf.put_u8(0); // unused
+
+    event
+}
+
+// action of a _NET_WM_STATE client message
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum WmStateAction {
+    Remove = 0,
+    Add = 1,
+    Toggle = 2,
+}
+
+// Byte order of a connection, chosen by the client in the connection
+// setup. The server uses it for its replies, events and errors too.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ByteOrder {
+    #[default]
+    LsbFirst,
+    MsbFirst,
+}
+
+impl ByteOrder {
+    // byte-order byte of the connection setup
+    pub(crate) const fn setup_byte(self) -> u8 {
+        match self {
+            Self::LsbFirst => 0x6c, // 'l'
+            Self::MsbFirst => 0x42, // 'B'
+        }
+    }
+
+    pub(crate) fn card16(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            Self::LsbFirst => u16::from_le_bytes(bytes),
+            Self::MsbFirst => u16::from_be_bytes(bytes),
+        }
+    }
+
+    pub(crate) fn card32(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            Self::LsbFirst => u32::from_le_bytes(bytes),
+            Self::MsbFirst => u32::from_be_bytes(bytes),
+        }
+    }
+
+    // encodes a CARD32, e.g. to compare it with the bytes of an event
+    #[must_use]
+    pub fn card32_bytes(self, value: u32) -> [u8; 4] {
+        match self {
+            Self::LsbFirst => value.to_le_bytes(),
+            Self::MsbFirst => value.to_be_bytes(),
+        }
+    }
+}
+
+// Requests or responses of a connection, which `OrderedBufMut` and
+// `OrderedBuf` write and read in the byte order of the connection.
+#[derive(Debug, Default)]
+pub struct Ordered<B> {
+    inner: B,
+    byte_order: ByteOrder,
+}
+
+impl<B> Ordered<B> {
+    pub fn new(inner: B, byte_order: ByteOrder) -> Self {
+        Self { inner, byte_order }
+    }
+
+    #[must_use]
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B> Deref for Ordered<B> {
+    type Target = B;
+
+    fn deref(&self) -> &B {
+        &self.inner
+    }
+}
+
+impl<B> DerefMut for Ordered<B> {
+    fn deref_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+}
+
+impl<B: Buf> Buf for Ordered<B> {
+    fn remaining(&self) -> usize {
+        self.inner.remaining()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.inner.chunk()
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        self.inner.advance(cnt);
+    }
+
+    // forwarded, so e.g. `Bytes` hands out the data without copying it
+    fn copy_to_bytes(&mut self, len: usize) -> Bytes {
+        self.inner.copy_to_bytes(len)
+    }
+}
+
+unsafe impl<B: BufMut> BufMut for Ordered<B> {
+    fn remaining_mut(&self) -> usize {
+        self.inner.remaining_mut()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        // SAFETY: forwarded with the guarantees of the caller
+        unsafe { self.inner.advance_mut(cnt) }
+    }
+
+    fn chunk_mut(&mut self) -> &mut bytes::buf::UninitSlice {
+        self.inner.chunk_mut()
+    }
+}
+
+// writes the numbers of the protocol in the byte order of the connection
+pub trait OrderedBufMut: BufMut {
+    fn put_card16(&mut self, value: u16);
+    fn put_card32(&mut self, value: u32);
+
+    fn put_int16(&mut self, value: i16) {
+        self.put_card16(value.cast_unsigned());
+    }
+
+    fn put_int32(&mut self, value: i32) {
+        self.put_card32(value.cast_unsigned());
+    }
+}
+
+impl<B: BufMut> OrderedBufMut for Ordered<B> {
+    fn put_card16(&mut self, value: u16) {
+        match self.byte_order {
+            ByteOrder::LsbFirst => self.inner.put_u16_le(value),
+            ByteOrder::MsbFirst => self.inner.put_u16(value),
+        }
+    }
+
+    fn put_card32(&mut self, value: u32) {
+        match self.byte_order {
+            ByteOrder::LsbFirst => self.inner.put_u32_le(value),
+            ByteOrder::MsbFirst => self.inner.put_u32(value),
+        }
+    }
+}
+
+impl<T: OrderedBufMut + ?Sized> OrderedBufMut for &mut T {
+    fn put_card16(&mut self, value: u16) {
+        (**self).put_card16(value);
+    }
+
+    fn put_card32(&mut self, value: u32) {
+        (**self).put_card32(value);
+    }
+}
+
+// reads the numbers of the protocol in the byte order of the connection
+pub trait OrderedBuf: Buf {
+    fn byte_order(&self) -> ByteOrder;
+    fn get_card16(&mut self) -> u16;
+    fn get_card32(&mut self) -> u32;
+
+    fn get_int16(&mut self) -> i16 {
+        self.get_card16().cast_signed()
+    }
+
+    fn get_int32(&mut self) -> i32 {
+        self.get_card32().cast_signed()
+    }
+}
+
+impl<B: Buf> OrderedBuf for Ordered<B> {
+    fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    fn get_card16(&mut self) -> u16 {
+        match self.byte_order {
+            ByteOrder::LsbFirst => self.inner.get_u16_le(),
+            ByteOrder::MsbFirst => self.inner.get_u16(),
+        }
+    }
+
+    fn get_card32(&mut self) -> u32 {
+        match self.byte_order {
+            ByteOrder::LsbFirst => self.inner.get_u32_le(),
+            ByteOrder::MsbFirst => self.inner.get_u32(),
+        }
+    }
+}
+
+impl<T: OrderedBuf + ?Sized> OrderedBuf for &mut T {
+    fn byte_order(&self) -> ByteOrder {
+        (**self).byte_order()
+    }
+
+    fn get_card16(&mut self) -> u16 {
+        (**self).get_card16()
+    }
+
+    fn get_card32(&mut self) -> u32 {
+        (**self).get_card32()
+    }
+}
+
+// pad(E) = (4 - (E mod 4)) mod 4
+pub(crate) const fn pad(len: usize) -> usize {
+    (4 - (len % 4)) % 4
+}
+
+#[derive(Debug)]
+pub struct LengthOverflow;
+
+impl fmt::Display for LengthOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "request or field length exceeds its protocol limit")
+    }
+}
+
+impl error::Error for LengthOverflow {}
+
+impl From<TryFromIntError> for LengthOverflow {
+    fn from(_: TryFromIntError) -> Self {
+        Self
+    }
+}
+
+impl From<LengthOverflow> for io::Error {
+    fn from(length_overflow: LengthOverflow) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, length_overflow)
+    }
+}
+
+// the ids of the resource id mask are used up and XC-MISC isn't in
+// use or has no further ids, see `Connection::use_xc_misc`
+#[derive(Debug)]
+pub struct IdsExhausted;
+
+impl fmt::Display for IdsExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no more resource ids")
+    }
+}
+
+impl error::Error for IdsExhausted {}
+
+impl From<IdsExhausted> for io::Error {
+    fn from(ids_exhausted: IdsExhausted) -> Self {
+        io::Error::other(ids_exhausted)
+    }
+}
+
+// takes the id of a new resource before anything of its request is
+// written, so the request buffer stays intact if there is none
+fn next_id(id_generator: &mut impl Iterator<Item = u32>) -> Result<u32, IdsExhausted> {
+    id_generator.next().ok_or(IdsExhausted)
+}
+
+// a request exceeds the maximum request length of the server, which
+// would close the connection if it was sent
+#[derive(Debug)]
+pub struct RequestTooLong {
+    pub request: String,
+    // in units of 4 bytes, like `maximum`
+    pub length: u32,
+    pub maximum: u32,
+}
+
+impl fmt::Display for RequestTooLong {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} words exceeds the maximum request length of {} words",
+            self.request, self.length, self.maximum
+        )
+    }
+}
+
+impl error::Error for RequestTooLong {}
+
+impl From<RequestTooLong> for io::Error {
+    fn from(request_too_long: RequestTooLong) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, request_too_long)
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    // the data ends before the named field or structure
+    UnexpectedEnd(&'static str),
+    // the named field has a value the protocol doesn't define
+    InvalidValue(&'static str, u32),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd(field) => write!(f, "data ends before {field}"),
+            Self::InvalidValue(field, value) => write!(f, "invalid {field}: {value}"),
+        }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+impl From<DecodeError> for io::Error {
+    fn from(decode_error: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, decode_error)
+    }
+}
+
+// fails unless `buf` holds at least `len` more bytes for `field`, so
+// the following reads can't panic
+fn ensure_remaining(
+    buf: &impl OrderedBuf,
+    len: usize,
+    field: &'static str,
+) -> Result<(), DecodeError> {
+    if buf.remaining() < len {
+        return Err(DecodeError::UnexpectedEnd(field));
+    }
+
+    Ok(())
+}
+
+// reads `len` bytes of the named field, which have to be ASCII
+fn get_ascii(
+    buf: &mut impl OrderedBuf,
+    len: usize,
+    field: &'static str,
+) -> Result<AsciiString, DecodeError> {
+    ensure_remaining(buf, len, field)?;
+    AsciiString::from_ascii(buf.copy_to_bytes(len).to_vec()).map_err(|error| {
+        let valid_up_to = error.ascii_error().valid_up_to();
+        let invalid_byte = error.into_source()[valid_up_to];
+        DecodeError::InvalidValue(field, invalid_byte.into())
+    })
+}
+
+// the request length in units of 4 bytes, of the fixed part and the
+// padded variable part
+pub fn request_len_words(fixed_bytes: usize, variable_bytes: usize) -> Result<u16, LengthOverflow> {
+    let bytes = fixed_bytes
+        .checked_add(variable_bytes)
+        .and_then(|bytes| bytes.checked_add(pad(variable_bytes)))
+        .ok_or(LengthOverflow)?;
+
+    Ok((bytes / 4).try_into()?)
+}
+
+// like `request_len_words`, beyond 16 bits the length includes the
+// extra 4 byte length word of a BIG-REQUESTS request
+pub fn big_request_len_words(
+    fixed_bytes: usize,
+    variable_bytes: usize,
+) -> Result<u32, LengthOverflow> {
+    let bytes = fixed_bytes
+        .checked_add(variable_bytes)
+        .and_then(|bytes| bytes.checked_add(pad(variable_bytes)))
+        .ok_or(LengthOverflow)?;
+    let words = bytes / 4;
+    if words > usize::from(u16::MAX) {
+        return Ok((words + 1).try_into()?);
+    }
+
+    Ok(words.try_into()?)
+}
+
+// writes the request length of `big_request_len_words`, a length of 0
+// followed by the 32 bit length for a BIG-REQUESTS request
+fn put_request_length(buf: &mut impl OrderedBufMut, request_length: u32) {
+    if let Ok(request_length) = u16::try_from(request_length) {
+        buf.put_card16(request_length); // request length
+    } else {
+        buf.put_card16(0); // request length, 0 for BIG-REQUESTS
+        buf.put_card32(request_length); // request length
+    }
+}
+
+pub(crate) fn map_window_request(buf: &mut impl OrderedBufMut, window_id: WindowId) {
+    buf.put_u8(Opcodes::MapWindow as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(window_id);
+}
+
+pub(crate) fn unmap_window_request(buf: &mut impl OrderedBufMut, window_id: WindowId) {
+    buf.put_u8(Opcodes::UnmapWindow as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(window_id);
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum StackModes {
+    Above = 0,
+    Below = 1,
+    TopIf = 2,
+    BottomIf = 3,
+    Opposite = 4,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum ConfigureWindowCommands {
+    X(i16),
+    Y(i16),
+    Width(u16),
+    Height(u16),
+    BorderWidth(u16),
+    Sibling(WindowId),
+    StackMode(StackModes),
+}
+
+impl ConfigureWindowCommands {
+    // bit of the command in the value-mask and its value
+    fn bit_and_value(self) -> (u16, u32) {
+        match self {
+            Self::X(x) => (0x0001, x.cast_unsigned().into()),
+            Self::Y(y) => (0x0002, y.cast_unsigned().into()),
+            Self::Width(width) => (0x0004, width.into()),
+            Self::Height(height) => (0x0008, height.into()),
+            Self::BorderWidth(border_width) => (0x0010, border_width.into()),
+            Self::Sibling(sibling) => (0x0020, sibling),
+            Self::StackMode(stack_mode) => (0x0040, stack_mode as u32),
+        }
+    }
+}
+
+pub(crate) fn configure_window(
+    buf: &mut impl OrderedBufMut,
+    window_id: WindowId,
+    commands: &[ConfigureWindowCommands],
+) -> Result<(), LengthOverflow> {
+    // values must be given in the order of their bits, a command given
+    // more than once overrides the previous one
+    let values: BTreeMap<u16, u32> = commands
+        .iter()
+        .map(|command| command.bit_and_value())
+        .collect();
+    let request_length = request_len_words(12, 4 * values.len())?;
+    buf.put_u8(Opcodes::ConfigureWindow as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(request_length); // request length
+    buf.put_card32(window_id);
+    buf.put_card16(values.keys().fold(0, |bitmask, bit| bitmask | bit)); // value-mask
+    buf.put_card16(0); // unused
+
+    // list-of-values
+    for value in values.values() {
+        buf.put_card32(*value);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn create_gc(
+    buf: &mut impl OrderedBufMut,
+    drawable: u32,
+    values: &[(CreateGcBits, u32)],
+    id_generator: &mut impl Iterator<Item = u32>,
+) -> io::Result<GCId> {
+    let values = value_list(values.iter().map(|&(bit, value)| (bit as u32, value)));
+    let request_length = request_len_words(16, 4 * values.len())?;
+    let id = next_id(id_generator)?;
+
+    buf.put_u8(Opcodes::CreateGC as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(request_length); // request length
+    buf.put_card32(id); // cid
+    buf.put_card32(drawable); // drawable
+    put_value_list(buf, &values);
+
+    Ok(id)
+}
+
+pub(crate) fn free_gc(buf: &mut impl OrderedBufMut, gc_id: GCId) {
+    buf.put_u8(Opcodes::FreeGC as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(gc_id);
+}
+
+pub(crate) fn create_pixmap(
+    buf: &mut impl OrderedBufMut,
+    depth: u8,
+    drawable: u32,
+    width: u16,
+    height: u16,
+    id_generator: &mut impl Iterator<Item = u32>,
+) -> Result<PixmapId, IdsExhausted> {
+    let id = next_id(id_generator)?;
+    buf.put_u8(Opcodes::CreatePixmap as u8); // opcode
+    buf.put_u8(depth); // depth
+    buf.put_card16(4); // request length
+    buf.put_card32(id); // pid
+    buf.put_card32(drawable); // drawable
+    buf.put_card16(width); // width
+    buf.put_card16(height); // height
+
+    Ok(id)
+}
+
+pub(crate) fn free_pixmap(buf: &mut impl OrderedBufMut, pixmap_id: PixmapId) {
+    buf.put_u8(Opcodes::FreePixmap as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(pixmap_id);
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum SizeClass {
+    Cursor = 0,
+    // fastest to tile with
+    Tile = 1,
+    // fastest to stipple with
+    Stipple = 2,
+}
+
+pub(crate) fn query_best_size(
+    buf: &mut impl OrderedBufMut,
+    class: SizeClass,
+    drawable: u32,
+    width: u16,
+    height: u16,
+) {
+    buf.put_u8(Opcodes::QueryBestSize as u8); // opcode
+    buf.put_u8(class as u8); // class
+    buf.put_card16(3); // request length
+    buf.put_card32(drawable); // drawable, determines the screen
+    buf.put_card16(width); // width
+    buf.put_card16(height); // height
+}
+
+#[derive(Debug)]
+pub struct QueryBestSizeReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl QueryBestSizeReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            width: buf.get_card16(),
+            height: buf.get_card16(),
+        };
+        buf.advance(20); // unused
+
+        this
+    }
+}
+
+// whether the entries of a new colormap are allocated by the client
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum ColormapAlloc {
+    None = 0,
+    // only for the classes with writable entries, GrayScale,
+    // PseudoColor and DirectColor
+    All = 1,
+}
+
+// creates a colormap of `visual` on the screen of `window_id`
+pub(crate) fn create_colormap(
+    buf: &mut impl OrderedBufMut,
+    alloc: ColormapAlloc,
+    window_id: WindowId,
+    visual: VisualId,
+    id_generator: &mut impl Iterator<Item = u32>,
+) -> Result<ColorMap, IdsExhausted> {
+    let id = next_id(id_generator)?;
+    buf.put_u8(Opcodes::CreateColormap as u8); // opcode
+    buf.put_u8(alloc as u8); // alloc
+    buf.put_card16(4); // request length
+    buf.put_card32(id); // mid
+    buf.put_card32(window_id); // window
+    buf.put_card32(visual); // visual
+
+    Ok(id)
+}
+
+pub(crate) fn free_colormap(buf: &mut impl OrderedBufMut, colormap: ColorMap) {
+    buf.put_u8(Opcodes::FreeColormap as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(2); // request length
+    buf.put_card32(colormap); // cmap
+}
+
+pub(crate) fn alloc_color(
+    buf: &mut impl OrderedBufMut,
+    colormap: ColorMap,
+    red: u16,
+    green: u16,
+    blue: u16,
+) {
+    buf.put_u8(Opcodes::AllocColor as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(4); // request length
+    buf.put_card32(colormap); // cmap
+    buf.put_card16(red); // red
+    buf.put_card16(green); // green
+    buf.put_card16(blue); // blue
+    buf.put_card16(0); // unused
+}
+
+#[derive(Debug)]
+pub struct AllocColorReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // the color actually allocated, the closest the hardware supports
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+    pub pixel: u32,
+}
+
+impl AllocColorReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let red = buf.get_card16();
+        let green = buf.get_card16();
+        let blue = buf.get_card16();
+        buf.advance(2); // unused
+        let pixel = buf.get_card32();
+        buf.advance(12); // unused
+
+        Self {
+            sequence_number,
+            reply_length,
+            red,
+            green,
+            blue,
+            pixel,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum GrabMode {
+    // event processing of the device is frozen until AllowEvents
+    Synchronous = 0,
+    Asynchronous = 1,
+}
+
+// Actively grabs the pointer. `event_mask` must be a subset of
+// `POINTER_EVENTS`, so e.g. key events and Exposure can't be selected.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn grab_pointer(
+    buf: &mut impl OrderedBufMut,
+    owner_events: bool,
+    grab_window: WindowId,
+    event_mask: BitFlags<Event>,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+    confine_to: WindowId,
+    cursor: u32,
+    time: u32,
+) -> io::Result<()> {
+    let invalid = event_mask & !POINTER_EVENTS;
+    let event_mask = match u16::try_from(event_mask.bits()) {
+        Ok(event_mask) if invalid.is_empty() => event_mask,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{invalid} aren't pointer events"),
+            ))
+        }
+    };
+
+    buf.put_u8(Opcodes::GrabPointer as u8); // opcode
+    buf.put_u8(owner_events.into()); // owner-events
+    buf.put_card16(6); // request length
+    buf.put_card32(grab_window); // grab-window
+    buf.put_card16(event_mask); // event-mask
+    buf.put_u8(pointer_mode as u8); // pointer-mode
+    buf.put_u8(keyboard_mode as u8); // keyboard-mode
+    buf.put_card32(confine_to); // confine-to, 0 is None
+    buf.put_card32(cursor); // cursor, 0 is None
+    buf.put_card32(time); // time, 0 is CurrentTime
+
+    Ok(())
+}
+
+pub(crate) fn grab_keyboard(
+    buf: &mut impl OrderedBufMut,
+    owner_events: bool,
+    grab_window: WindowId,
+    time: u32,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+) {
+    buf.put_u8(Opcodes::GrabKeyboard as u8); // opcode
+    buf.put_u8(owner_events.into()); // owner-events
+    buf.put_card16(4); // request length
+    buf.put_card32(grab_window); // grab-window
+    buf.put_card32(time); // time, 0 is CurrentTime
+    buf.put_u8(pointer_mode as u8); // pointer-mode
+    buf.put_u8(keyboard_mode as u8); // keyboard-mode
+    buf.put_card16(0); // unused
+}
+
+// releases an active grab of the pointer, unless `time` is earlier
+// than the time of the grab
+pub(crate) fn ungrab_pointer(buf: &mut impl OrderedBufMut, time: u32) {
+    buf.put_u8(Opcodes::UngrabPointer as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(2); // request length
+    buf.put_card32(time); // time, 0 is CurrentTime
+}
+
+pub(crate) fn ungrab_keyboard(buf: &mut impl OrderedBufMut, time: u32) {
+    buf.put_u8(Opcodes::UngrabKeyboard as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(2); // request length
+    buf.put_card32(time); // time, 0 is CurrentTime
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GrabStatus {
+    Success = 0,
+    AlreadyGrabbed = 1,
+    InvalidTime = 2,
+    NotViewable = 3,
+    Frozen = 4,
+}
+
+impl GrabStatus {
+    // AlreadyGrabbed and Frozen are caused by a grab of another
+    // client, which is usually released shortly
+    #[must_use]
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::AlreadyGrabbed | Self::Frozen)
+    }
+}
+
+// reply of GrabPointer and GrabKeyboard
+#[derive(Debug)]
+pub struct GrabReply {
+    pub status: GrabStatus,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+}
+
+impl GrabReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "grab reply")?;
+        buf.advance(1); // reply
+        let this = Self {
+            status: match buf.get_u8() {
+                0 => GrabStatus::Success,
+                1 => GrabStatus::AlreadyGrabbed,
+                2 => GrabStatus::InvalidTime,
+                3 => GrabStatus::NotViewable,
+                4 => GrabStatus::Frozen,
+                other => return Err(DecodeError::InvalidValue("status", other.into())),
+            },
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+        };
+        buf.advance(24); // unused
+
+        Ok(this)
+    }
+}
+
+// arguments of GrabPointer, see `Connection::grab_pointer_retry`
+#[derive(Copy, Clone, Debug)]
+pub struct GrabPointerParams {
+    pub owner_events: bool,
+    pub grab_window: WindowId,
+    pub event_mask: BitFlags<Event>,
+    pub pointer_mode: GrabMode,
+    pub keyboard_mode: GrabMode,
+    pub confine_to: WindowId,
+    pub cursor: u32,
+    pub time: u32,
+}
+
+// any button of GrabButton and UngrabButton
+pub const ANY_BUTTON: u8 = 0;
+// any key of GrabKey and UngrabKey
+pub const ANY_KEY: u8 = 0;
+// any combination of modifiers, including none
+pub const ANY_MODIFIER: u16 = 0x8000;
+
+// Passively grabs `button` with `modifiers` on `grab_window`, i.e. the
+// pointer is grabbed as soon as the button is pressed.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn grab_button(
+    buf: &mut impl OrderedBufMut,
+    owner_events: bool,
+    grab_window: WindowId,
+    event_mask: u16,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+    confine_to: WindowId,
+    cursor: u32,
+    button: u8,
+    modifiers: u16,
+) {
+    buf.put_u8(Opcodes::GrabButton as u8); // opcode
+    buf.put_u8(owner_events.into()); // owner-events
+    buf.put_card16(6); // request length
+    buf.put_card32(grab_window); // grab-window
+    buf.put_card16(event_mask); // event-mask, SETofPOINTEREVENT
+    buf.put_u8(pointer_mode as u8); // pointer-mode
+    buf.put_u8(keyboard_mode as u8); // keyboard-mode
+    buf.put_card32(confine_to); // confine-to, 0 is None
+    buf.put_card32(cursor); // cursor, 0 is None
+    buf.put_u8(button); // button
+    buf.put_u8(0); // unused
+    buf.put_card16(modifiers); // modifiers
+}
+
+pub(crate) fn ungrab_button(
+    buf: &mut impl OrderedBufMut,
+    button: u8,
+    grab_window: WindowId,
+    modifiers: u16,
+) {
+    buf.put_u8(Opcodes::UngrabButton as u8); // opcode
+    buf.put_u8(button); // button
+    buf.put_card16(3); // request length
+    buf.put_card32(grab_window); // grab-window
+    buf.put_card16(modifiers); // modifiers
+    buf.put_card16(0); // unused
+}
+
+// Passively grabs `key` with `modifiers` on `grab_window`, i.e. the
+// keyboard is grabbed as soon as the key is pressed.
+pub(crate) fn grab_key(
+    buf: &mut impl OrderedBufMut,
+    owner_events: bool,
+    grab_window: WindowId,
+    modifiers: u16,
+    key: u8,
+    pointer_mode: GrabMode,
+    keyboard_mode: GrabMode,
+) {
+    buf.put_u8(Opcodes::GrabKey as u8); // opcode
+    buf.put_u8(owner_events.into()); // owner-events
+    buf.put_card16(4); // request length
+    buf.put_card32(grab_window); // grab-window
+    buf.put_card16(modifiers); // modifiers
+    buf.put_u8(key); // key
+    buf.put_u8(pointer_mode as u8); // pointer-mode
+    buf.put_u8(keyboard_mode as u8); // keyboard-mode
+    buf.put_bytes(0, 3); // unused
+}
+
+pub(crate) fn ungrab_key(
+    buf: &mut impl OrderedBufMut,
+    key: u8,
+    grab_window: WindowId,
+    modifiers: u16,
+) {
+    buf.put_u8(Opcodes::UngrabKey as u8); // opcode
+    buf.put_u8(key); // key
+    buf.put_card16(3); // request length
+    buf.put_card32(grab_window); // grab-window
+    buf.put_card16(modifiers); // modifiers
+    buf.put_card16(0); // unused
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum AllowEventsMode {
+    AsyncPointer = 0,
+    SyncPointer = 1,
+    ReplayPointer = 2,
+    AsyncKeyboard = 3,
+    SyncKeyboard = 4,
+    ReplayKeyboard = 5,
+    AsyncBoth = 6,
+    SyncBoth = 7,
+}
+
+// releases events frozen by a synchronous grab
+pub(crate) fn allow_events(buf: &mut impl OrderedBufMut, mode: AllowEventsMode, time: u32) {
+    buf.put_u8(Opcodes::AllowEvents as u8); // opcode
+    buf.put_u8(mode as u8); // mode
+    buf.put_card16(2); // request length
+    buf.put_card32(time); // time, 0 is CurrentTime
+}
+
+pub(crate) fn grab_server(buf: &mut impl OrderedBufMut) {
+    buf.put_u8(Opcodes::GrabServer as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(1); // request length
+}
+
+pub(crate) fn ungrab_server(buf: &mut impl OrderedBufMut) {
+    buf.put_u8(Opcodes::UngrabServer as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(1); // request length
+}
+
+pub(crate) fn query_pointer(buf: &mut impl OrderedBufMut, window_id: WindowId) {
+    buf.put_u8(Opcodes::QueryPointer as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(window_id); // window
+}
+
+#[derive(Debug)]
+pub struct QueryPointerReply {
+    pub same_screen: bool,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub root: WindowId,
+    // 0 is None
+    pub child: WindowId,
+    pub root_x: i16,
+    pub root_y: i16,
+    // relative to the window of the request, only meaningful if
+    // `same_screen` is set
+    pub win_x: i16,
+    pub win_y: i16,
+    pub mask: BitFlags<KeyButMask>,
+}
+
+impl QueryPointerReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // reply
+        let this = Self {
+            same_screen: buf.get_u8() != 0,
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            root: buf.get_card32(),
+            child: buf.get_card32(),
+            root_x: buf.get_int16(),
+            root_y: buf.get_int16(),
+            win_x: buf.get_int16(),
+            win_y: buf.get_int16(),
+            mask: BitFlags::from_bits_truncate(buf.get_card16()),
+        };
+        buf.advance(6); // unused
+
+        this
+    }
+}
+
+// Moves the pointer to (`dst_x`, `dst_y`) relative to `dst_window`,
+// or by that offset if `dst_window` is 0 (None). With a `src_window`
+// other than 0 (None), the pointer only moves if it is inside the
+// given rectangle of `src_window`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn warp_pointer(
+    buf: &mut impl OrderedBufMut,
+    src_window: WindowId,
+    dst_window: WindowId,
+    src_x: i16,
+    src_y: i16,
+    src_width: u16,
+    src_height: u16,
+    dst_x: i16,
+    dst_y: i16,
+) {
+    buf.put_u8(Opcodes::WarpPointer as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(6); // request length
+    buf.put_card32(src_window); // src-window
+    buf.put_card32(dst_window); // dst-window
+    buf.put_int16(src_x); // src-x
+    buf.put_int16(src_y); // src-y
+    buf.put_card16(src_width); // src-width
+    buf.put_card16(src_height); // src-height
+    buf.put_int16(dst_x); // dst-x
+    buf.put_int16(dst_y); // dst-y
+}
+
+pub(crate) fn get_input_focus(buf: &mut impl OrderedBufMut) {
+    buf.put_u8(Opcodes::GetInputFocus as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(1); // request length
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Focus {
+    None,
+    PointerRoot,
+    Window(WindowId),
+}
+
+impl Focus {
+    fn from_u32(focus: u32) -> Self {
+        match focus {
+            0 => Self::None,
+            1 => Self::PointerRoot,
+            window_id => Self::Window(window_id),
+        }
+    }
+}
+
+// where the focus reverts to if the focus window becomes unviewable
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RevertTo {
+    None = 0,
+    PointerRoot = 1,
+    Parent = 2,
+}
+
+pub(crate) fn set_input_focus(
+    buf: &mut impl OrderedBufMut,
+    revert_to: RevertTo,
+    focus: WindowId,
+    time: u32,
+) {
+    buf.put_u8(Opcodes::SetInputFocus as u8); // opcode
+    buf.put_u8(revert_to as u8); // revert-to
+    buf.put_card16(3); // request length
+    buf.put_card32(focus); // focus, 0 is None, 1 is PointerRoot
+    buf.put_card32(time); // time, 0 is CurrentTime
+}
+
+#[derive(Debug)]
+pub struct GetInputFocusReply {
+    pub revert_to: RevertTo,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub focus: Focus,
+}
+
+impl GetInputFocusReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "GetInputFocus reply")?;
+        buf.advance(1); // reply
+        let this = Self {
+            revert_to: match buf.get_u8() {
+                0 => RevertTo::None,
+                1 => RevertTo::PointerRoot,
+                2 => RevertTo::Parent,
+                other => return Err(DecodeError::InvalidValue("revert-to", other.into())),
+            },
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            focus: Focus::from_u32(buf.get_card32()),
+        };
+        buf.advance(20); // unused
+
+        Ok(this)
+    }
+}
+
+pub(crate) fn list_fonts(buf: &mut impl OrderedBufMut) -> Result<(), LengthOverflow> {
+    let pattern = b"*";
+    let request_length = request_len_words(8, pattern.len())?;
+    let pattern_length: u16 = pattern.len().try_into()?;
+
+    buf.put_u8(Opcodes::ListFonts as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(request_length); // request length
+    buf.put_card16(1000); // max-names
+    buf.put_card16(pattern_length); // length of pattern
+    buf.put_slice(pattern); // pattern
+
+    buf.put_bytes(0, pad(pattern.len()));
+
+    Ok(())
+}
+
+pub(crate) fn list_fonts_with_info(
+    buf: &mut impl OrderedBufMut,
+    max_names: u16,
+    pattern: &[u8],
+) -> Result<(), LengthOverflow> {
+    let request_length = request_len_words(8, pattern.len())?;
+    let pattern_length: u16 = pattern.len().try_into()?;
+
+    buf.put_u8(Opcodes::ListFontsWithInfo as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(request_length); // request length
+    buf.put_card16(max_names); // max-names
+    buf.put_card16(pattern_length); // length of pattern
+    buf.put_slice(pattern); // pattern
+
+    buf.put_bytes(0, pad(pattern.len()));
+
+    Ok(())
+}
+
+pub(crate) fn query_extension(
+    buf: &mut impl OrderedBufMut,
+    extension_name: &[u8],
+) -> Result<(), LengthOverflow> {
+    let n = extension_name.len();
+    let request_length = request_len_words(8, n)?;
+    let name_length: u16 = n.try_into()?;
+    buf.put_u8(Opcodes::QueryExtension as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(request_length); // request length
+    buf.put_card16(name_length); // length of name
+    buf.put_card16(0); // unused
+    buf.put_slice(extension_name);
+    buf.put_bytes(0, pad(n));
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct ListFontsReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub names: Vec<AsciiString>,
+}
+
+impl ListFontsReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "ListFonts reply")?;
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let number_of_names = buf.get_card16();
+        buf.advance(22); // unused, up to the 32 bytes of every reply
+
+        let mut names = Vec::with_capacity(number_of_names.into());
+        for _name in 0..number_of_names {
+            ensure_remaining(buf, 1, "name")?;
+            let name_length = buf.get_u8() as usize;
+            names.push(get_ascii(buf, name_length, "name")?);
+        }
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            names,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryExtensionReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub present: bool,
+    pub major_opcode: u8,
+    pub first_event: u8,
+    pub first_error: u8,
+}
+
+impl QueryExtensionReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            present: buf.get_u8() != 0,
+            major_opcode: buf.get_u8(),
+            first_event: buf.get_u8(),
+            first_error: buf.get_u8(),
+        };
+        buf.advance(20); // unused
+
+        this
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ExtensionInfo {
+    pub major_opcode: u8,
+    pub first_event: u8,
+    pub first_error: u8,
+}
+
+#[derive(Debug)]
+pub struct ListExtensionsReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub names: Vec<AsciiString>,
+}
+
+impl ListExtensionsReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "ListExtensions reply")?;
+        buf.advance(1); // reply
+        let number_of_names = buf.get_u8();
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        buf.advance(24); // unused
+
+        let mut names = Vec::with_capacity(number_of_names.into());
+        for _name in 0..number_of_names {
+            ensure_remaining(buf, 1, "name")?;
+            let name_length = buf.get_u8() as usize;
+            names.push(get_ascii(buf, name_length, "name")?);
+        }
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            names,
+        })
+    }
+}
+
+pub(crate) fn list_extensions(buf: &mut impl OrderedBufMut) {
+    buf.put_u8(Opcodes::ListExtensions as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(1); // request length
+}
+
+pub(crate) fn open_font(
+    buf: &mut impl OrderedBufMut,
+    id_generator: &mut impl Iterator<Item = u32>,
+) -> io::Result<u32> {
+    let font_name = b"fixed";
+    let request_length = request_len_words(12, font_name.len())?;
+    let font_name_length: u16 = font_name.len().try_into().map_err(LengthOverflow::from)?;
+    let font_id = next_id(id_generator)?;
+    buf.put_u8(Opcodes::OpenFont as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(request_length); // request length
+    buf.put_card32(font_id); // font ID
+    buf.put_card16(font_name_length); // length of name
+    buf.put_card16(0); // unused
+    buf.put_slice(font_name); // name of font
+    buf.put_bytes(0, pad(font_name.len()));
+
+    Ok(font_id)
+}
+
+pub(crate) fn close_font(buf: &mut impl OrderedBufMut, font_id: u32) {
+    buf.put_u8(Opcodes::CloseFont as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(font_id);
+}
+
+// `fontable` is either a font or a graphics context, in which case
+// the font of the graphics context is queried
+pub(crate) fn query_font(buf: &mut impl OrderedBufMut, fontable: u32) {
+    buf.put_u8(Opcodes::QueryFont as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(2); // request length
+    buf.put_card32(fontable); // font
+}
+
+// metrics of a character, or the minimum or maximum of each metric of
+// all characters of a font
+#[derive(Copy, Clone, Debug)]
+pub struct CharInfo {
+    pub left_side_bearing: i16,
+    pub right_side_bearing: i16,
+    pub character_width: i16,
+    pub ascent: i16,
+    pub descent: i16,
+    pub attributes: u16,
+}
+
+impl CharInfo {
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        Self {
+            left_side_bearing: buf.get_int16(),
+            right_side_bearing: buf.get_int16(),
+            character_width: buf.get_int16(),
+            ascent: buf.get_int16(),
+            descent: buf.get_int16(),
+            attributes: buf.get_card16(),
+        }
+    }
+}
+
+// a property of a font like FONT or POINT_SIZE, the value is an atom
+// for properties with string values
+#[derive(Copy, Clone, Debug)]
+pub struct FontProperty {
+    pub name: Atom,
+    pub value: u32,
+}
+
+impl FontProperty {
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        Self {
+            name: buf.get_card32(),
+            value: buf.get_card32(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct QueryFontReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub min_bounds: CharInfo,
+    pub max_bounds: CharInfo,
+    pub min_char_or_byte2: u16,
+    pub max_char_or_byte2: u16,
+    pub default_char: u16,
+    // 0 LeftToRight, 1 RightToLeft
+    pub draw_direction: u8,
+    pub min_byte1: u8,
+    pub max_byte1: u8,
+    pub all_chars_exist: bool,
+    pub font_ascent: i16,
+    pub font_descent: i16,
+    pub properties: Vec<FontProperty>,
+    // Metrics of each character from the first to the last of the
+    // font, row by row for fonts with two byte characters. Empty if
+    // all characters have the same metrics, the min and max bounds.
+    pub char_infos: Vec<CharInfo>,
+}
+
+impl QueryFontReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 60, "QueryFont reply")?;
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let min_bounds = CharInfo::from_bytes(buf);
+        buf.advance(4); // unused
+        let max_bounds = CharInfo::from_bytes(buf);
+        buf.advance(4); // unused
+        let min_char_or_byte2 = buf.get_card16();
+        let max_char_or_byte2 = buf.get_card16();
+        let default_char = buf.get_card16();
+        let number_of_properties = usize::from(buf.get_card16());
+        let draw_direction = buf.get_u8();
+        let min_byte1 = buf.get_u8();
+        let max_byte1 = buf.get_u8();
+        let all_chars_exist = buf.get_u8() != 0;
+        let font_ascent = buf.get_int16();
+        let font_descent = buf.get_int16();
+        let number_of_char_infos = buf.get_card32() as usize;
+        ensure_remaining(buf, 8 * number_of_properties, "font properties")?;
+        let properties = (0..number_of_properties)
+            .map(|_property| FontProperty::from_bytes(buf))
+            .collect();
+        ensure_remaining(buf, number_of_char_infos.saturating_mul(12), "char infos")?;
+        let char_infos = (0..number_of_char_infos)
+            .map(|_char_info| CharInfo::from_bytes(buf))
+            .collect();
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            min_bounds,
+            max_bounds,
+            min_char_or_byte2,
+            max_char_or_byte2,
+            default_char,
+            draw_direction,
+            min_byte1,
+            max_byte1,
+            all_chars_exist,
+            font_ascent,
+            font_descent,
+            properties,
+            char_infos,
+        })
+    }
+}
+
+// a font matching the pattern of ListFontsWithInfo, the same as a
+// QueryFont reply without the metrics of each character
+#[derive(Debug)]
+pub struct FontWithInfo {
+    pub name: AsciiString,
+    pub min_bounds: CharInfo,
+    pub max_bounds: CharInfo,
+    pub min_char_or_byte2: u16,
+    pub max_char_or_byte2: u16,
+    pub default_char: u16,
+    // 0 LeftToRight, 1 RightToLeft
+    pub draw_direction: u8,
+    pub min_byte1: u8,
+    pub max_byte1: u8,
+    pub all_chars_exist: bool,
+    pub font_ascent: i16,
+    pub font_descent: i16,
+    pub properties: Vec<FontProperty>,
+    // a guess how many more fonts follow, not to be relied on
+    pub replies_hint: u32,
+}
+
+impl FontWithInfo {
+    // `None` for the last reply of ListFontsWithInfo, which only
+    // terminates the series
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Option<Self>, DecodeError> {
+        ensure_remaining(buf, 60, "ListFontsWithInfo reply")?;
+        buf.advance(1); // reply
+        let name_length = usize::from(buf.get_u8());
+        if name_length == 0 {
+            return Ok(None);
+        }
+        buf.advance(6); // sequence number, reply length
+        let min_bounds = CharInfo::from_bytes(buf);
+        buf.advance(4); // unused
+        let max_bounds = CharInfo::from_bytes(buf);
+        buf.advance(4); // unused
+        let min_char_or_byte2 = buf.get_card16();
+        let max_char_or_byte2 = buf.get_card16();
+        let default_char = buf.get_card16();
+        let number_of_properties = usize::from(buf.get_card16());
+        let draw_direction = buf.get_u8();
+        let min_byte1 = buf.get_u8();
+        let max_byte1 = buf.get_u8();
+        let all_chars_exist = buf.get_u8() != 0;
+        let font_ascent = buf.get_int16();
+        let font_descent = buf.get_int16();
+        let replies_hint = buf.get_card32();
+        ensure_remaining(buf, 8 * number_of_properties, "font properties")?;
+        let properties = (0..number_of_properties)
+            .map(|_property| FontProperty::from_bytes(buf))
+            .collect();
+        ensure_remaining(buf, name_length, "font name")?;
+        let name =
+            AsciiString::from_ascii(buf.copy_to_bytes(name_length).to_vec()).map_err(|error| {
+                let valid_up_to = error.ascii_error().valid_up_to();
+                let invalid_byte = error.into_source()[valid_up_to];
+                DecodeError::InvalidValue("font name", invalid_byte.into())
+            })?;
+
+        Ok(Some(Self {
+            name,
+            min_bounds,
+            max_bounds,
+            min_char_or_byte2,
+            max_char_or_byte2,
+            default_char,
+            draw_direction,
+            min_byte1,
+            max_byte1,
+            all_chars_exist,
+            font_ascent,
+            font_descent,
+            properties,
+            replies_hint,
+        }))
+    }
+}
+
+pub(crate) fn image_text_8(
+    buf: &mut impl OrderedBufMut,
+    window_id: u32,
+    gc_id: u32,
+    x: i16,
+    y: i16,
+    text: &[u8],
+) -> Result<(), LengthOverflow> {
+    let text_length = text.len();
+    let request_length = request_len_words(16, text_length)?;
+    let string_length: u8 = text_length.try_into()?;
+    buf.put_u8(Opcodes::ImageText8 as u8); // opcode
+    buf.put_u8(string_length); // length of string
+    buf.put_card16(request_length); // request length
+    buf.put_card32(window_id); // drawable
+    buf.put_card32(gc_id); // context
+    buf.put_int16(x); // x
+    buf.put_int16(y); // y
+    buf.put_slice(text);
+    buf.put_bytes(0, pad(text_length));
+
+    Ok(())
+}
+
+// like `image_text_8`, but for fonts indexed by CHAR2B, the string
+// length is the number of characters
+pub(crate) fn image_text_16(
+    buf: &mut impl OrderedBufMut,
+    drawable: u32,
+    gc_id: GCId,
+    x: i16,
+    y: i16,
+    text: &[u16],
+) -> Result<(), LengthOverflow> {
+    let n = text.len() * 2;
+    let request_length = request_len_words(16, n)?;
+    let string_length: u8 = text.len().try_into()?;
+    buf.put_u8(Opcodes::ImageText16 as u8); // opcode
+    buf.put_u8(string_length); // length of string
+    buf.put_card16(request_length); // request length
+    buf.put_card32(drawable); // drawable
+    buf.put_card32(gc_id); // gc
+    buf.put_int16(x); // x
+    buf.put_int16(y); // y
+    for &char2b in text {
+        buf.put_u16(char2b); // CHAR2B is always sent as byte1, byte2
+    }
+    buf.put_bytes(0, pad(n));
+
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Rectangle {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Point {
+    pub x: i16,
+    pub y: i16,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum CoordinateMode {
+    // all points are relative to the origin of the drawable
+    Origin = 0,
+    // each point but the first is relative to the previous one
+    Previous = 1,
+}
+
+fn put_point(buf: &mut impl OrderedBufMut, point: Point) {
+    buf.put_int16(point.x);
+    buf.put_int16(point.y);
+}
+
+fn put_rectangle(buf: &mut impl OrderedBufMut, rectangle: Rectangle) {
+    buf.put_int16(rectangle.x);
+    buf.put_int16(rectangle.y);
+    buf.put_card16(rectangle.width);
+    buf.put_card16(rectangle.height);
+}
+
+// draws lines connecting the points, joined according to the join-style
+// of the GC, the line is closed if the last point equals the first
+pub(crate) fn poly_line(
+    buf: &mut impl OrderedBufMut,
+    coordinate_mode: CoordinateMode,
+    drawable: u32,
+    gc_id: GCId,
+    points: &[Point],
+) -> Result<(), LengthOverflow> {
+    let request_length = big_request_len_words(12, 4 * points.len())?;
+    buf.put_u8(Opcodes::PolyLine as u8); // opcode
+    buf.put_u8(coordinate_mode as u8); // coordinate-mode
+    put_request_length(buf, request_length);
+    buf.put_card32(drawable); // drawable
+    buf.put_card32(gc_id); // gc
+    for point in points {
+        put_point(buf, *point);
+    }
+
+    Ok(())
+}
+
+// draws a separate line from the first to the second point of each
+// segment
+pub(crate) fn poly_segment(
+    buf: &mut impl OrderedBufMut,
+    drawable: u32,
+    gc_id: GCId,
+    segments: &[(Point, Point)],
+) -> Result<(), LengthOverflow> {
+    let request_length = big_request_len_words(12, 8 * segments.len())?;
+    buf.put_u8(Opcodes::PolySegment as u8); // opcode
+    buf.put_u8(0); // unused
+    put_request_length(buf, request_length);
+    buf.put_card32(drawable); // drawable
+    buf.put_card32(gc_id); // gc
+    for (start, end) in segments {
+        put_point(buf, *start);
+        put_point(buf, *end);
+    }
+
+    Ok(())
+}
+
+// draws the outlines of the rectangles, which span width + 1 and
+// height + 1 pixels
+pub(crate) fn poly_rectangle(
+    buf: &mut impl OrderedBufMut,
+    drawable: u32,
+    gc_id: GCId,
+    rectangles: &[Rectangle],
+) -> Result<(), LengthOverflow> {
+    let request_length = big_request_len_words(12, 8 * rectangles.len())?;
+    buf.put_u8(Opcodes::PolyRectangle as u8); // opcode
+    buf.put_u8(0); // unused
+    put_request_length(buf, request_length);
+    buf.put_card32(drawable); // drawable
+    buf.put_card32(gc_id); // gc
+    for rectangle in rectangles {
+        put_rectangle(buf, *rectangle);
+    }
+
+    Ok(())
+}
+
+// Clears a rectangle of `window_id` to its background. A width or
+// height of 0 extends the rectangle to the right or bottom edge of the
+// window. With `exposures`, Expose events are generated for the
+// cleared regions which are visible or backed.
+pub(crate) fn clear_area(
+    buf: &mut impl OrderedBufMut,
+    exposures: bool,
+    window_id: WindowId,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+) {
+    buf.put_u8(Opcodes::ClearArea as u8); // opcode
+    buf.put_u8(exposures.into()); // exposures
+    buf.put_card16(4); // request length
+    buf.put_card32(window_id); // window
+    buf.put_int16(x); // x
+    buf.put_int16(y); // y
+    buf.put_card16(width); // width, 0 up to the edge of the window
+    buf.put_card16(height); // height, 0 up to the edge of the window
+}
+
+// Copies a rectangle of `src` to `dst`, which must have the same root
+// and depth. Regions of `src` which are obscured or outside of it
+// aren't copied, they are reported by GraphicsExposure events if the
+// GC has graphics-exposures set, otherwise a NoExposure follows.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn copy_area(
+    buf: &mut impl OrderedBufMut,
+    src: u32,
+    dst: u32,
+    gc_id: GCId,
+    src_x: i16,
+    src_y: i16,
+    dst_x: i16,
+    dst_y: i16,
+    width: u16,
+    height: u16,
+) {
+    buf.put_u8(Opcodes::CopyArea as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(7); // request length
+    buf.put_card32(src); // src-drawable
+    buf.put_card32(dst); // dst-drawable
+    buf.put_card32(gc_id); // gc
+    buf.put_int16(src_x); // src-x
+    buf.put_int16(src_y); // src-y
+    buf.put_int16(dst_x); // dst-x
+    buf.put_int16(dst_y); // dst-y
+    buf.put_card16(width); // width
+    buf.put_card16(height); // height
+}
+
+// Part of the ellipse inscribed in the rectangle at `x` and `y` of
+// `width` and `height`, from `angle1` relative to 3 o'clock, extending
+// `angle2` relative to `angle1`. The angles are in 1/64 degrees and
+// positive counterclockwise.
+#[derive(Copy, Clone, Debug)]
+pub struct Arc {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub angle1: i16,
+    pub angle2: i16,
+}
+
+fn put_arcs(
+    buf: &mut impl OrderedBufMut,
+    opcode: Opcodes,
+    drawable: u32,
+    gc_id: GCId,
+    arcs: &[Arc],
+) -> Result<(), LengthOverflow> {
+    let request_length = big_request_len_words(12, 12 * arcs.len())?;
+    buf.put_u8(opcode as u8); // opcode
+    buf.put_u8(0); // unused
+    put_request_length(buf, request_length);
+    buf.put_card32(drawable); // drawable
+    buf.put_card32(gc_id); // gc
+    for arc in arcs {
+        buf.put_int16(arc.x);
+        buf.put_int16(arc.y);
+        buf.put_card16(arc.width);
+        buf.put_card16(arc.height);
+        buf.put_int16(arc.angle1);
+        buf.put_int16(arc.angle2);
+    }
+
+    Ok(())
+}
+
+// draws the outlines of the arcs, e.g. a circle for a square with
+// `angle2` of 360 * 64
+pub(crate) fn poly_arc(
+    buf: &mut impl OrderedBufMut,
+    drawable: u32,
+    gc_id: GCId,
+    arcs: &[Arc],
+) -> Result<(), LengthOverflow> {
+    put_arcs(buf, Opcodes::PolyArc, drawable, gc_id, arcs)
+}
+
+// fills the arcs, as pie slices or chords depending on the arc-mode of
+// the GC
+pub(crate) fn poly_fill_arc(
+    buf: &mut impl OrderedBufMut,
+    drawable: u32,
+    gc_id: GCId,
+    arcs: &[Arc],
+) -> Result<(), LengthOverflow> {
+    put_arcs(buf, Opcodes::PolyFillArc, drawable, gc_id, arcs)
+}
+
+pub(crate) fn poly_fill_rectangle(
+    buf: &mut impl OrderedBufMut,
+    drawable: u32,
+    gc_id: GCId,
+    rectangles: &[Rectangle],
+) -> Result<(), LengthOverflow> {
+    let request_length = big_request_len_words(12, 8 * rectangles.len())?;
+    buf.put_u8(Opcodes::PolyFillRectangle as u8); // opcode
+    buf.put_u8(0); // unused
+    put_request_length(buf, request_length);
+    buf.put_card32(drawable); // drawable
+    buf.put_card32(gc_id); // gc
+    for rectangle in rectangles {
+        put_rectangle(buf, *rectangle);
+    }
+
+    Ok(())
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum ImageFormat {
+    Bitmap = 0,
+    XYPixmap = 1,
+    ZPixmap = 2,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn put_image(
+    buf: &mut impl OrderedBufMut,
+    format: ImageFormat,
+    drawable: u32,
+    gc_id: GCId,
+    width: u16,
+    height: u16,
+    dst_x: i16,
+    dst_y: i16,
+    left_pad: u8,
+    depth: u8,
+    data: &[u8],
+) -> Result<(), LengthOverflow> {
+    let n = data.len();
+    let request_length = big_request_len_words(24, n)?;
+    buf.put_u8(Opcodes::PutImage as u8); // opcode
+    buf.put_u8(format as u8); // format
+    put_request_length(buf, request_length);
+    buf.put_card32(drawable); // drawable
+    buf.put_card32(gc_id); // gc
+    buf.put_card16(width); // width
+    buf.put_card16(height); // height
+    buf.put_int16(dst_x); // dst-x
+    buf.put_int16(dst_y); // dst-y
+    buf.put_u8(left_pad); // left-pad
+    buf.put_u8(depth); // depth
+    buf.put_card16(0); // unused
+    buf.put_slice(data);
+    buf.put_bytes(0, pad(n));
+
+    Ok(())
+}
+
+// `format` must be XYPixmap or ZPixmap, only the planes in
+// `plane_mask` are returned
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn get_image(
+    buf: &mut impl OrderedBufMut,
+    format: ImageFormat,
+    drawable: u32,
+    x: i16,
+    y: i16,
+    width: u16,
+    height: u16,
+    plane_mask: u32,
+) {
+    buf.put_u8(Opcodes::GetImage as u8); // opcode
+    buf.put_u8(format as u8); // format
+    buf.put_card16(5); // request length
+    buf.put_card32(drawable); // drawable
+    buf.put_int16(x); // x
+    buf.put_int16(y); // y
+    buf.put_card16(width); // width
+    buf.put_card16(height); // height
+    buf.put_card32(plane_mask); // plane-mask
+}
+
+#[derive(Debug)]
+pub struct GetImageReply {
+    pub depth: u8,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // `None` for a pixmap
+    pub visual: Option<VisualId>,
+    // the image, encoded according to the format and the image format
+    // of the setup, padded to 4 bytes
+    pub data: Bytes,
+}
+
+impl GetImageReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "GetImage reply")?;
+        buf.advance(1); // reply
+        let depth = buf.get_u8();
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let visual = Some(buf.get_card32()).filter(|&visual| visual != 0);
+        buf.advance(20); // unused
+        let n = (reply_length as usize).saturating_mul(4);
+        ensure_remaining(buf, n, "data")?;
+        let data = buf.copy_to_bytes(n);
+
+        Ok(Self {
+            depth,
+            sequence_number,
+            reply_length,
+            visual,
+            data,
+        })
+    }
+}
+
+// `fontable` is either a font or a graphics context, in which case
+// the font of the graphics context is used
+pub(crate) fn query_text_extents(
+    buf: &mut impl OrderedBufMut,
+    fontable: u32,
+    text: &[u16],
+) -> Result<(), LengthOverflow> {
+    let n = text.len() * 2;
+    let request_length = request_len_words(8, n)?;
+    buf.put_u8(Opcodes::QueryTextExtents as u8); // opcode
+    buf.put_u8(u8::from(text.len() % 2 == 1)); // odd length
+    buf.put_card16(request_length); // request length
+    buf.put_card32(fontable);
+    for &char2b in text {
+        buf.put_u16(char2b); // CHAR2B is always sent as byte1, byte2
+    }
+    buf.put_bytes(0, pad(n));
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct QueryTextExtentsReply {
+    pub draw_direction: u8,
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub font_ascent: i16,
+    pub font_descent: i16,
+    pub overall_ascent: i16,
+    pub overall_descent: i16,
+    pub overall_width: i32,
+    pub overall_left: i32,
+    pub overall_right: i32,
+}
+
+impl QueryTextExtentsReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // reply
+        let this = Self {
+            draw_direction: buf.get_u8(),
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            font_ascent: buf.get_int16(),
+            font_descent: buf.get_int16(),
+            overall_ascent: buf.get_int16(),
+            overall_descent: buf.get_int16(),
+            overall_width: buf.get_int32(),
+            overall_left: buf.get_int32(),
+            overall_right: buf.get_int32(),
+        };
+        buf.advance(4); // unused
+
+        this
+    }
+}
+
+pub(crate) fn get_keyboard_mapping(buf: &mut impl OrderedBufMut, first_keycode: u8, count: u8) {
+    buf.put_u8(Opcodes::GetKeyboardMapping as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(2); // request length
+    buf.put_u8(first_keycode); // first-keycode
+    buf.put_u8(count); // count
+    buf.put_card16(0); // unused
+}
+
+// the keysyms of `count` keycodes, `keysyms_per_keycode` for every
+// keycode starting at the first keycode of the request, 0 is NoSymbol
+#[derive(Debug)]
+pub struct GetKeyboardMappingReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub keysyms_per_keycode: u8,
+    pub keysyms: Vec<u32>,
+}
+
+impl GetKeyboardMappingReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "GetKeyboardMapping reply")?;
+        buf.advance(1); // reply
+        let keysyms_per_keycode = buf.get_u8();
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        buf.advance(24); // unused
+        let number_of_keysyms = reply_length as usize;
+        ensure_remaining(buf, number_of_keysyms.saturating_mul(4), "keysyms")?;
+        let keysyms = (0..number_of_keysyms)
+            .map(|_keysym| buf.get_card32())
+            .collect();
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            keysyms_per_keycode,
+            keysyms,
+        })
+    }
+
+    // The keysyms of `keycode`, e.g. of a KeyPress event, if the
+    // mapping was requested starting at `first_keycode`. Empty if the
+    // keycode isn't part of the mapping.
+    #[must_use]
+    pub fn keysyms_of(&self, first_keycode: u8, keycode: u8) -> &[u32] {
+        let per_keycode = usize::from(self.keysyms_per_keycode);
+        let Some(index) = keycode.checked_sub(first_keycode) else {
+            return &[];
+        };
+        let start = usize::from(index) * per_keycode;
+        self.keysyms.get(start..start + per_keycode).unwrap_or(&[])
+    }
+}
+
+#[bitflags]
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum KeyboardControlBits {
+    KeyClickPercent = 0x01,
+    BellPercent = 0x02,
+    BellPitch = 0x04,
+    BellDuration = 0x08,
+    Led = 0x10,
+    LedMode = 0x20,
+    Key = 0x40,
+    AutoRepeatMode = 0x80,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum LedMode {
+    Off = 0,
+    On = 1,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u32)]
+pub enum AutoRepeatMode {
+    Off = 0,
+    On = 1,
+    Default = 2,
+}
+
+// a keyboard setting with its value, see `change_keyboard_control`
+#[derive(Copy, Clone, Debug)]
+pub enum KeyboardControl {
+    // 0 to 100, -1 restores the default
+    KeyClickPercent(i8),
+    BellPercent(i8),
+    // in Hz, -1 restores the default
+    BellPitch(i16),
+    // in milliseconds, -1 restores the default
+    BellDuration(i16),
+    // the LED `LedMode` applies to, 1 to 32, all LEDs if not given
+    Led(u8),
+    LedMode(LedMode),
+    // the key `AutoRepeatMode` applies to, all keys if not given
+    Key(u8),
+    AutoRepeatMode(AutoRepeatMode),
+}
+
+impl KeyboardControl {
+    // bit of the setting in the value-mask and its value, signed values
+    // are sign extended to 32 bits
+    fn bit_and_value(self) -> (KeyboardControlBits, u32) {
+        match self {
+            Self::KeyClickPercent(percent) => (
+                KeyboardControlBits::KeyClickPercent,
+                i32::from(percent).cast_unsigned(),
+            ),
+            Self::BellPercent(percent) => (
+                KeyboardControlBits::BellPercent,
+                i32::from(percent).cast_unsigned(),
+            ),
+            Self::BellPitch(pitch) => (
+                KeyboardControlBits::BellPitch,
+                i32::from(pitch).cast_unsigned(),
+            ),
+            Self::BellDuration(duration) => (
+                KeyboardControlBits::BellDuration,
+                i32::from(duration).cast_unsigned(),
+            ),
+            Self::Led(led) => (KeyboardControlBits::Led, led.into()),
+            Self::LedMode(led_mode) => (KeyboardControlBits::LedMode, led_mode as u32),
+            Self::Key(key) => (KeyboardControlBits::Key, key.into()),
+            Self::AutoRepeatMode(auto_repeat_mode) => {
+                (KeyboardControlBits::AutoRepeatMode, auto_repeat_mode as u32)
+            }
+        }
+    }
+}
+
+pub(crate) fn change_keyboard_control(
+    buf: &mut impl OrderedBufMut,
+    values: &[KeyboardControl],
+) -> io::Result<()> {
+    let mut value_list = BTreeMap::new();
+    for value in values {
+        let (bit, value) = value.bit_and_value();
+        if value_list.insert(bit as u32, value).is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("keyboard control {bit:?} given more than once"),
+            ));
+        }
+    }
+    let request_length = request_len_words(8, 4 * value_list.len())?;
+
+    buf.put_u8(Opcodes::ChangeKeyboardControl as u8); // opcode
+    buf.put_u8(0); // unused
+    buf.put_card16(request_length); // request length
+    put_value_list(buf, &value_list);
+
+    Ok(())
+}
+
+// rings the bell, `percent` is relative to the base volume of the
+// bell percent of ChangeKeyboardControl
+pub(crate) fn bell(buf: &mut impl OrderedBufMut, percent: i8) -> io::Result<()> {
+    if !(-100..=100).contains(&percent) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("bell percent {percent} isn't between -100 and 100"),
+        ));
+    }
+
+    buf.put_u8(Opcodes::Bell as u8); // opcode
+    buf.put_u8(percent.cast_unsigned()); // percent
+    buf.put_card16(1); // request length
+
+    Ok(())
+}
+
+pub(crate) fn get_screen_saver(buf: &mut impl OrderedBufMut) {
+    buf.put_u8(Opcodes::GetScreenSaver as u8); // opcode
+    buf.put_u8(0); // padding
+    buf.put_card16(1); // request length
+}
+
+// prefer-blanking and allow-exposures of the screen saver, `Default`
+// only occurs when setting them and restores the server default
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScreenSaverMode {
+    No = 0,
+    Yes = 1,
+    Default = 2,
+}
+
+impl ScreenSaverMode {
+    fn from_u8(value: u8, field: &'static str) -> Result<Self, DecodeError> {
+        match value {
+            0 => Ok(Self::No),
+            1 => Ok(Self::Yes),
+            2 => Ok(Self::Default),
+            other => Err(DecodeError::InvalidValue(field, other.into())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GetScreenSaverReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // in seconds, 0 means the screen saver is disabled
+    pub timeout: u16,
+    // in seconds between changes of the pattern when not blanking, 0
+    // means the pattern never changes
+    pub interval: u16,
+    pub prefer_blanking: ScreenSaverMode,
+    pub allow_exposures: ScreenSaverMode,
+}
+
+impl GetScreenSaverReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "GetScreenSaver reply")?;
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            timeout: buf.get_card16(),
+            interval: buf.get_card16(),
+            prefer_blanking: ScreenSaverMode::from_u8(buf.get_u8(), "prefer-blanking")?,
+            allow_exposures: ScreenSaverMode::from_u8(buf.get_u8(), "allow-exposures")?,
+        };
+        buf.advance(18); // unused
+
+        Ok(this)
+    }
+
+    #[must_use]
+    pub fn is_disabled(&self) -> bool {
+        self.timeout == 0
+    }
+}
+
+// 1 bit per pixel image, e.g. for Shape masks and cursors, stored
+// row by row with the leftmost pixel in the least significant bit
+#[derive(Clone, Debug)]
+pub struct Bitmap {
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+    data: Vec<u8>,
+}
+
+impl Bitmap {
+    #[must_use]
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; Self::stride(width) * usize::from(height)],
+        }
+    }
+
+    fn stride(width: u16) -> usize {
+        usize::from(width).div_ceil(8)
+    }
+
+    #[must_use]
+    pub fn get(&self, x: u16, y: u16) -> bool {
+        let index = usize::from(y) * Self::stride(self.width) + usize::from(x / 8);
+        self.data[index] & (1 << (x % 8)) != 0
+    }
+
+    pub fn set(&mut self, x: u16, y: u16, value: bool) {
+        assert!(x < self.width && y < self.height, "pixel out of bounds");
+        let index = usize::from(y) * Self::stride(self.width) + usize::from(x / 8);
+        if value {
+            self.data[index] |= 1 << (x % 8);
+        } else {
+            self.data[index] &= !(1 << (x % 8));
+        }
+    }
+
+    // Encodes the bitmap as expected by PutImage in Bitmap or XYPixmap
+    // format. Each scanline is split into scanline units, whose bits
+    // are ordered by the bitmap format bit order of the server and
+    // whose bytes are ordered by the image byte order of the server.
+    pub(crate) fn encode(&self, setup: &Setup, buf: &mut impl OrderedBufMut) {
+        let unit = usize::from(setup.bitmap_format_scanline_unit);
+        let scanline_pad = usize::from(setup.bitmap_format_scanline_pad);
+        let scanline_bits = usize::from(self.width).div_ceil(scanline_pad) * scanline_pad;
+        for y in 0..self.height {
+            for unit_start in (0..scanline_bits).step_by(unit) {
+                let mut value = 0u32;
+                for bit in 0..unit {
+                    let x = unit_start + bit;
+                    if x < usize::from(self.width) && self.get(x.try_into().unwrap(), y) {
+                        value |= match setup.bitmap_format_bit_order {
+                            BitmapFormatBitOrder::LeastSignificant => 1 << bit,
+                            BitmapFormatBitOrder::MostSignificant => 1 << (unit - 1 - bit),
+                        };
+                    }
+                }
+                match setup.image_byte_order {
+                    ImageByteOrder::LSBFirst => buf.put_slice(&value.to_le_bytes()[..unit / 8]),
+                    ImageByteOrder::MSBFirst => {
+                        buf.put_slice(&value.to_be_bytes()[4 - unit / 8..]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// an error the server sent in response to a request
+#[derive(Debug)]
+pub struct XError {
+    // an `ErrorCode`, or an error of an extension
+    pub code: u8,
+    pub sequence_number: u16,
+    // the bad resource id, atom or value, if the error reports one
+    pub bad_value: Option<u32>,
+    pub minor_opcode: u16,
+    pub major_opcode: u8,
+}
+
+impl XError {
+    // `None` for errors of extensions
+    #[must_use]
+    pub fn error_code(&self) -> Option<ErrorCode> {
+        ErrorCode::from_u8(self.code)
+    }
+
+    // `buf` holds exactly the 32 bytes of the error, so whatever fields
+    // the error carries, the bytes following it are left untouched
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // error
+        let code = buf.get_u8();
+        let sequence_number = buf.get_card16();
+        let bad_value = match ErrorCode::from_u8(code) {
+            Some(
+                ErrorCode::Value
+                | ErrorCode::Window
+                | ErrorCode::Pixmap
+                | ErrorCode::Atom
+                | ErrorCode::Cursor
+                | ErrorCode::Font
+                | ErrorCode::Drawable
+                | ErrorCode::Colormap
+                | ErrorCode::GContext
+                | ErrorCode::IDChoice,
+            ) => Some(buf.get_card32()),
+            Some(
+                ErrorCode::Request
+                | ErrorCode::Match
+                | ErrorCode::Access
+                | ErrorCode::Alloc
+                | ErrorCode::Name
+                | ErrorCode::Length
+                | ErrorCode::Implementation,
+            ) => {
+                buf.advance(4); // unused
+                None
+            }
+            None => {
+                // errors of extensions, the field is specific to them
+                buf.advance(4);
+                None
+            }
+        };
+        let minor_opcode = buf.get_card16();
+        let major_opcode = buf.get_u8();
+        buf.advance(21); // unused
+
+        Self {
+            code,
+            sequence_number,
+            bad_value,
+            minor_opcode,
+            major_opcode,
+        }
+    }
+}
+
+impl fmt::Display for XError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.error_code() {
+            Some(error_code) => write!(f, "{error_code:?} error")?,
+            None => write!(f, "error {}", self.code)?,
+        }
+        write!(f, " of request {}", self.sequence_number)?;
+        match Opcodes::from_u8(self.major_opcode) {
+            Some(opcode) => write!(f, " ({opcode:?})")?,
+            None => write!(
+                f,
+                " (extension {} minor {})",
+                self.major_opcode, self.minor_opcode
+            )?,
+        }
+        if let Some(bad_value) = self.bad_value {
+            write!(f, ", bad value {bad_value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl error::Error for XError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+
+    fn request_buf() -> Ordered<BytesMut> {
+        Ordered::new(BytesMut::new(), ByteOrder::LsbFirst)
+    }
+
+    fn reply_buf(reply: &[u8]) -> Ordered<&[u8]> {
+        Ordered::new(reply, ByteOrder::LsbFirst)
+    }
+
+    #[test]
+    fn create_requests_fail_without_ids() {
+        let mut buf = request_buf();
+
+        assert!(create_pixmap(&mut buf, 1, 0x100, 16, 16, &mut std::iter::empty()).is_err());
+        assert!(create_colormap(
+            &mut buf,
+            ColormapAlloc::None,
+            0x100,
+            0x21,
+            &mut std::iter::empty()
+        )
+        .is_err());
+        assert!(create_gc(&mut buf, 0x100, &[], &mut std::iter::empty()).is_err());
+        assert!(open_font(&mut buf, &mut std::iter::empty()).is_err());
+        assert!(buf.is_empty(), "partial request written: {:x?}", &buf[..]);
+    }
+
+    #[test]
+    fn grab_pointer_only_accepts_pointer_events() {
+        for event_mask in [
+            make_bitflags!(Event::{KeyPress}),
+            make_bitflags!(Event::{KeyRelease | ButtonPress}),
+            make_bitflags!(Event::{Exposure}),
+            make_bitflags!(Event::{StructureNotify}),
+        ] {
+            let mut buf = request_buf();
+            let result = grab_pointer(
+                &mut buf,
+                false,
+                0x100,
+                event_mask,
+                GrabMode::Asynchronous,
+                GrabMode::Asynchronous,
+                0,
+                0,
+                0,
+            );
+            assert!(result.is_err(), "{event_mask} accepted");
+            assert!(buf.is_empty());
+        }
+
+        let mut buf = request_buf();
+        grab_pointer(
+            &mut buf,
+            false,
+            0x100,
+            POINTER_EVENTS,
+            GrabMode::Asynchronous,
+            GrabMode::Asynchronous,
+            0,
+            0,
+            0,
+        )
+        .unwrap();
+        assert_eq!(buf[8..10], [0xfc, 0x7f]);
+    }
+
+    #[test]
+    fn get_atom_name_reply_decodes_latin_1() {
+        let mut reply = vec![1, 0, 7, 0, 2, 0, 0, 0, 5, 0];
+        reply.resize(32, 0);
+        reply.extend_from_slice(b"caf\xe9s\0\0\0");
+
+        let reply = GetAtomNameReply::from_bytes(&mut reply_buf(&reply)).unwrap();
+        assert_eq!(reply.sequence_number, 7);
+        assert_eq!(reply.name, "caf\u{e9}s");
+    }
+
+    #[test]
+    fn get_atom_name_reply_fails_on_a_short_name() {
+        let mut reply = vec![1, 0, 7, 0, 2, 0, 0, 0, 200, 0];
+        reply.resize(40, 0);
+
+        assert!(matches!(
+            GetAtomNameReply::from_bytes(&mut reply_buf(&reply)),
+            Err(DecodeError::UnexpectedEnd("name"))
+        ));
+    }
+
+    #[test]
+    fn window_attributes_reply_fails_on_an_unknown_map_state() {
+        let mut reply = vec![0; 44];
+        reply[0] = 1;
+        reply[26] = 3; // map-state
+
+        assert!(matches!(
+            WindowAttributesReply::from_bytes(&mut reply_buf(&reply)),
+            Err(DecodeError::InvalidValue("map-state", 3))
+        ));
+    }
+
+    #[test]
+    fn close_font_request() {
+        let mut buf = request_buf();
+        close_font(&mut buf, 0x0040_0003);
+
+        assert_eq!(buf[..], [46, 0, 2, 0, 0x03, 0x00, 0x40, 0x00]);
+    }
+
+    #[test]
+    fn get_geometry_reply_field_offsets() {
+        let reply = [
+            1, 24, 5, 0, 0, 0, 0, 0, // reply, depth, sequence number, reply length
+            0x2b, 0x01, 0x00, 0x00, 0xf6, 0xff, 0x14, 0x00, // root, x, y
+            0x2c, 0x01, 0xc8, 0x00, 0x02, 0x00, 0, 0, // width, height, border width
+            0, 0, 0, 0, 0, 0, 0, 0, // unused
+        ];
+
+        let reply = GetGeometryReply::from_bytes(&mut reply_buf(&reply));
+        assert_eq!(reply.depth, 24);
+        assert_eq!(reply.sequence_number, 5);
+        assert_eq!(reply.root, 0x12b);
+        assert_eq!((reply.x, reply.y), (-10, 20));
+        assert_eq!((reply.width, reply.height), (300, 200));
+        assert_eq!(reply.border_width, 2);
+    }
+
+    #[test]
+    fn alloc_color_request() {
+        let mut buf = request_buf();
+        alloc_color(&mut buf, 0x20, 0x1234, 0x5678, 0x9abc);
+
+        assert_eq!(buf.len(), 16);
+        assert_eq!(buf[2..4], [4, 0]); // request length
+        assert_eq!(buf[8..14], [0x34, 0x12, 0x78, 0x56, 0xbc, 0x9a]);
+    }
+
+    #[test]
+    fn image_text_16_request() {
+        let text = [0x0048, 0x0069, 0x2603]; // "Hi" and U+2603
+        let mut buf = request_buf();
+        image_text_16(&mut buf, 0x0040_0001, 0x0040_0002, 10, 20, &text).unwrap();
+
+        assert_eq!(
+            buf[..],
+            [
+                77, 3, 6, 0, // opcode, length of string, request length
+                0x01, 0x00, 0x40, 0x00, // drawable
+                0x02, 0x00, 0x40, 0x00, // gc
+                10, 0, 20, 0, // x, y
+                0x00, 0x48, 0x00, 0x69, 0x26, 0x03, 0, 0, // CHAR2Bs, pad
+            ]
+        );
+
+        // CHAR2B doesn't depend on the byte order of the connection
+        let mut msb_buf = Ordered::new(BytesMut::new(), ByteOrder::MsbFirst);
+        image_text_16(&mut msb_buf, 0x0040_0001, 0x0040_0002, 10, 20, &text).unwrap();
+        assert_eq!(msb_buf[16..], buf[16..]);
+    }
+
+    #[test]
+    fn query_text_extents_request_matches_libx11() {
+        let text = [u16::from(b'H'), u16::from(b'i')];
+        let mut buf = request_buf();
+        query_text_extents(&mut buf, 0x0040_0003, &text).unwrap();
+
+        // as sent by XQueryTextExtents16 for "Hi"
+        assert_eq!(
+            buf[..],
+            [48, 0, 3, 0, 0x03, 0x00, 0x40, 0x00, 0, b'H', 0, b'i']
+        );
+
+        let mut buf = request_buf();
+        query_text_extents(&mut buf, 0x0040_0003, &text[..1]).unwrap();
+        assert_eq!(buf[..4], [48, 1, 3, 0]); // odd length
+        assert_eq!(buf[8..], [0, b'H', 0, 0]);
+    }
+
+    #[test]
+    fn warp_pointer_absolute_and_relative() {
+        let mut buf = request_buf();
+        warp_pointer(&mut buf, 0, 0x100, 0, 0, 0, 0, 300, 200);
+        assert_eq!(
+            buf[..],
+            [
+                41, 0, 6, 0, // opcode, padding, request length
+                0, 0, 0, 0, 0x00, 0x01, 0, 0, // src-window, dst-window
+                0, 0, 0, 0, 0, 0, 0, 0, // source rectangle
+                0x2c, 0x01, 0xc8, 0, // dst-x, dst-y
+            ]
+        );
+
+        // moves by (-5, 10) from wherever the pointer is
+        let mut buf = request_buf();
+        warp_pointer(&mut buf, 0, 0, 0, 0, 0, 0, -5, 10);
+        assert_eq!(buf[4..12], [0; 8]); // src-window, dst-window
+        assert_eq!(buf[20..], [0xfb, 0xff, 10, 0]);
+    }
+
+    #[test]
+    fn allow_events_mode_is_the_second_header_byte() {
+        for (mode, byte) in [
+            (AllowEventsMode::AsyncPointer, 0),
+            (AllowEventsMode::ReplayPointer, 2),
+            (AllowEventsMode::SyncBoth, 7),
+        ] {
+            let mut buf = request_buf();
+            allow_events(&mut buf, mode, 0x1234_5678);
+            assert_eq!(buf[..], [35, byte, 2, 0, 0x78, 0x56, 0x34, 0x12]);
+        }
+    }
+
+    #[test]
+    fn ungrab_requests() {
+        let mut buf = request_buf();
+        ungrab_pointer(&mut buf, 0);
+        ungrab_keyboard(&mut buf, 0x1234_5678);
+
+        assert_eq!(
+            buf[..],
+            [27, 0, 2, 0, 0, 0, 0, 0, 32, 0, 2, 0, 0x78, 0x56, 0x34, 0x12]
+        );
+    }
+
+    #[test]
+    fn create_colormap_alloc_is_the_second_header_byte() {
+        for (alloc, byte) in [(ColormapAlloc::None, 0), (ColormapAlloc::All, 1)] {
+            let mut buf = request_buf();
+            let id = create_colormap(
+                &mut buf,
+                alloc,
+                0x100,
+                0x21,
+                &mut std::iter::once(0x0040_0001),
+            )
+            .unwrap();
+
+            assert_eq!(id, 0x0040_0001);
+            assert_eq!(
+                buf[..],
+                [78, byte, 4, 0, 0x01, 0, 0x40, 0, 0x00, 0x01, 0, 0, 0x21, 0, 0, 0]
+            );
+        }
+    }
+
+    #[test]
+    fn key_press_keycode_maps_to_keysyms() {
+        // GetKeyboardMapping for keycodes 37 to 39, 2 keysyms each
+        let mut reply = vec![1, 2, 9, 0, 6, 0, 0, 0];
+        reply.resize(32, 0);
+        for keysym in [0xffe3_u32, 0, 0x61, 0x41, 0x73, 0x53] {
+            reply.extend_from_slice(&keysym.to_le_bytes());
+        }
+        let mapping = GetKeyboardMappingReply::from_bytes(&mut reply_buf(&reply)).unwrap();
+
+        // KeyPress of keycode 38, without the event code
+        let mut event = vec![38, 10, 0];
+        event.resize(31, 0);
+        let Some(DecodedEvent::KeyPress(key_press)) =
+            decode_event(Events::KeyPress, &mut reply_buf(&event))
+        else {
+            panic!("KeyPress not decoded");
+        };
+
+        assert_eq!(mapping.keysyms_of(37, key_press.detail), [0x61, 0x41]);
+        assert_eq!(mapping.keysyms_of(37, 36), []);
+        assert_eq!(mapping.keysyms_of(37, 40), []);
+    }
+
+    #[test]
+    fn decoders_do_not_panic_on_random_bytes() {
+        let decoders: [fn(&mut Ordered<&[u8]>); 17] = [
+            |buf| drop(Setup::from_bytes(buf)),
+            |buf| drop(WindowAttributesReply::from_bytes(buf)),
+            |buf| drop(GetAtomNameReply::from_bytes(buf)),
+            |buf| drop(GrabReply::from_bytes(buf)),
+            |buf| drop(GetInputFocusReply::from_bytes(buf)),
+            |buf| drop(QueryFontReply::from_bytes(buf)),
+            |buf| drop(FontWithInfo::from_bytes(buf)),
+            |buf| drop(GetKeyboardMappingReply::from_bytes(buf)),
+            |buf| drop(GetScreenSaverReply::from_bytes(buf)),
+            |buf| drop(ShapeGetRectanglesReply::from_bytes(buf)),
+            |buf| drop(QueryTreeReply::from_bytes(buf)),
+            |buf| drop(GetPropertyReply::from_bytes(buf)),
+            |buf| drop(ListFontsReply::from_bytes(buf)),
+            |buf| drop(ListExtensionsReply::from_bytes(buf)),
+            |buf| drop(GetImageReply::from_bytes(buf)),
+            |buf| drop(XvQueryAdaptorsReply::from_bytes(buf)),
+            |buf| drop(XvQueryEncodingsReply::from_bytes(buf)),
+        ];
+        // a linear congruential generator, good enough to vary the
+        // lengths and counts the decoders rely on
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut random = move || {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            state.to_be_bytes()[0]
+        };
+
+        for _ in 0..10_000 {
+            let len = usize::from(random()) + usize::from(random());
+            let mut bytes: Vec<u8> = (0..len).map(|_| random()).collect();
+            if bytes.len() >= 32 && random() % 2 == 0 {
+                // get past the first checks of the setup: a short
+                // ASCII vendor, few formats and valid byte orders
+                bytes[16..18].copy_from_slice(&[random() % 8, 0]);
+                bytes[21] %= 4;
+                bytes[22] %= 2;
+                bytes[23] %= 2;
+                for byte in bytes.iter_mut().skip(32).take(8) {
+                    *byte &= 0x7f;
+                }
+            }
+            for decode in decoders {
+                decode(&mut reply_buf(&bytes));
+            }
+        }
+    }
+
+    #[test]
+    fn query_tree_reply_fails_on_more_children_than_sent() {
+        let mut reply = vec![1, 0, 3, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 2, 0];
+        reply.resize(32, 0);
+        reply.extend_from_slice(&0x0040_0001_u32.to_le_bytes());
+
+        assert!(matches!(
+            QueryTreeReply::from_bytes(&mut reply_buf(&reply)),
+            Err(DecodeError::UnexpectedEnd("children"))
+        ));
+    }
+
+    #[test]
+    fn get_property_reply_checks_format_and_value() {
+        // a STRING property of 5 bytes, format 8
+        let mut reply = vec![1, 8, 4, 0, 2, 0, 0, 0, 31, 0, 0, 0, 0, 0, 0, 0, 5, 0];
+        reply.resize(32, 0);
+        reply.extend_from_slice(b"hello\0\0\0");
+
+        let decoded = GetPropertyReply::from_bytes(&mut reply_buf(&reply)).unwrap();
+        assert_eq!(decoded.type_, 31);
+        assert_eq!(&decoded.value[..], b"hello");
+
+        assert!(matches!(
+            GetPropertyReply::from_bytes(&mut reply_buf(&reply[..36])),
+            Err(DecodeError::UnexpectedEnd("value"))
+        ));
+
+        reply[1] = 24;
+        assert!(matches!(
+            GetPropertyReply::from_bytes(&mut reply_buf(&reply)),
+            Err(DecodeError::InvalidValue("format", 24))
+        ));
+    }
+}
@@ -0,0 +1,43 @@
+use super::{OrderedBuf, OrderedBufMut};
+
+// BIG-REQUESTS extension, negotiated via `Connection::query_extension`
+// with "BIG-REQUESTS". Once enabled, requests may exceed the 16 bit
+// request length, see `big_request_len_words`.
+pub struct BigRequestsExtension {
+    major_opcode: u8,
+}
+
+impl BigRequestsExtension {
+    #[must_use]
+    pub fn new(major_opcode: u8) -> Self {
+        Self { major_opcode }
+    }
+
+    pub(crate) fn enable(&self, buf: &mut impl OrderedBufMut) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(0); // big-requests opcode
+        buf.put_card16(1); // request length
+    }
+}
+
+#[derive(Debug)]
+pub struct BigRequestsEnableReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // in units of 4 bytes
+    pub maximum_request_length: u32,
+}
+
+impl BigRequestsEnableReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            maximum_request_length: buf.get_card32(),
+        };
+        buf.advance(20); // unused
+
+        this
+    }
+}
@@ -0,0 +1,584 @@
+use super::{Atom, Events, KeyButMask, MappingNotifyRequest, OrderedBuf, WindowId};
+use bytes::Bytes;
+use enumflags2::BitFlags;
+use num_traits::FromPrimitive;
+use std::any::Any;
+use std::fmt;
+
+// KeyPress, KeyRelease, ButtonPress, ButtonRelease and MotionNotify
+// share this layout, the detail is the keycode, the button or whether
+// the event is a hint
+#[derive(Debug)]
+pub struct InputEvent {
+    pub detail: u8,
+    pub sequence_number: u16,
+    pub time: u32,
+    pub root: WindowId,
+    pub event: WindowId,
+    // the child of `event` containing the pointer, 0 if there is none
+    pub child: WindowId,
+    pub root_x: i16,
+    pub root_y: i16,
+    // the pointer position relative to `event`
+    pub event_x: i16,
+    pub event_y: i16,
+    pub state: BitFlags<KeyButMask>,
+    pub same_screen: bool,
+}
+
+impl InputEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        let this = Self {
+            detail: buf.get_u8(),
+            sequence_number: buf.get_card16(),
+            time: buf.get_card32(),
+            root: buf.get_card32(),
+            event: buf.get_card32(),
+            child: buf.get_card32(),
+            root_x: buf.get_int16(),
+            root_y: buf.get_int16(),
+            event_x: buf.get_int16(),
+            event_y: buf.get_int16(),
+            state: BitFlags::from_bits_truncate(buf.get_card16()),
+            same_screen: buf.get_u8() != 0,
+        };
+        buf.advance(1); // unused
+
+        this
+    }
+}
+
+// EnterNotify and LeaveNotify
+#[derive(Debug)]
+pub struct CrossingEvent {
+    // 0 Ancestor, 1 Virtual, 2 Inferior, 3 Nonlinear, 4 NonlinearVirtual
+    pub detail: u8,
+    pub sequence_number: u16,
+    pub time: u32,
+    pub root: WindowId,
+    pub event: WindowId,
+    pub child: WindowId,
+    pub root_x: i16,
+    pub root_y: i16,
+    pub event_x: i16,
+    pub event_y: i16,
+    pub state: BitFlags<KeyButMask>,
+    // 0 Normal, 1 Grab, 2 Ungrab
+    pub mode: u8,
+    pub same_screen: bool,
+    pub focus: bool,
+}
+
+impl CrossingEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        let detail = buf.get_u8();
+        let sequence_number = buf.get_card16();
+        let time = buf.get_card32();
+        let root = buf.get_card32();
+        let event = buf.get_card32();
+        let child = buf.get_card32();
+        let (root_x, root_y) = (buf.get_int16(), buf.get_int16());
+        let (event_x, event_y) = (buf.get_int16(), buf.get_int16());
+        let state = BitFlags::from_bits_truncate(buf.get_card16());
+        let mode = buf.get_u8();
+        let same_screen_focus = buf.get_u8();
+
+        Self {
+            detail,
+            sequence_number,
+            time,
+            root,
+            event,
+            child,
+            root_x,
+            root_y,
+            event_x,
+            event_y,
+            state,
+            mode,
+            same_screen: same_screen_focus & 0x2 != 0,
+            focus: same_screen_focus & 0x1 != 0,
+        }
+    }
+}
+
+// a region of a window which has to be repainted
+#[derive(Debug)]
+pub struct ExposeEvent {
+    pub sequence_number: u16,
+    pub window: WindowId,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    // number of Expose events which follow for the same window, 0 for
+    // the last one
+    pub count: u16,
+}
+
+impl ExposeEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            window: buf.get_card32(),
+            x: buf.get_card16(),
+            y: buf.get_card16(),
+            width: buf.get_card16(),
+            height: buf.get_card16(),
+            count: buf.get_card16(),
+        };
+        buf.advance(14); // unused
+
+        this
+    }
+}
+
+// a region of the destination of CopyArea or CopyPlane which couldn't
+// be copied, because the source region is obscured
+#[derive(Debug)]
+pub struct GraphicsExposureEvent {
+    pub sequence_number: u16,
+    pub drawable: u32,
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+    pub minor_opcode: u16,
+    // number of GraphicsExposure events which follow for the same
+    // request, 0 for the last one
+    pub count: u16,
+    pub major_opcode: u8,
+}
+
+impl GraphicsExposureEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            drawable: buf.get_card32(),
+            x: buf.get_card16(),
+            y: buf.get_card16(),
+            width: buf.get_card16(),
+            height: buf.get_card16(),
+            minor_opcode: buf.get_card16(),
+            count: buf.get_card16(),
+            major_opcode: buf.get_u8(),
+        };
+        buf.advance(11); // unused
+
+        this
+    }
+}
+
+// CopyArea or CopyPlane could copy everything
+#[derive(Debug)]
+pub struct NoExposureEvent {
+    pub sequence_number: u16,
+    pub drawable: u32,
+    pub minor_opcode: u16,
+    pub major_opcode: u8,
+}
+
+impl NoExposureEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            drawable: buf.get_card32(),
+            minor_opcode: buf.get_card16(),
+            major_opcode: buf.get_u8(),
+        };
+        buf.advance(21); // unused
+
+        this
+    }
+}
+
+// a window got a new parent by ReparentWindow
+#[derive(Debug)]
+pub struct ReparentNotifyEvent {
+    pub sequence_number: u16,
+    // the window itself, or its old or new parent with
+    // SubstructureNotify
+    pub event: WindowId,
+    pub window: WindowId,
+    pub parent: WindowId,
+    // relative to the origin of the new parent
+    pub x: i16,
+    pub y: i16,
+    pub override_redirect: bool,
+}
+
+impl ReparentNotifyEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            event: buf.get_card32(),
+            window: buf.get_card32(),
+            parent: buf.get_card32(),
+            x: buf.get_int16(),
+            y: buf.get_int16(),
+            override_redirect: buf.get_u8() != 0,
+        };
+        buf.advance(11); // unused
+
+        this
+    }
+}
+
+// a window changed its position, size, border width or stacking order
+#[derive(Debug)]
+pub struct ConfigureNotifyEvent {
+    pub sequence_number: u16,
+    // the window itself, or its parent with SubstructureNotify
+    pub event: WindowId,
+    pub window: WindowId,
+    // the sibling the window is stacked on top of, `None` at the bottom
+    pub above_sibling: Option<WindowId>,
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub border_width: u16,
+    pub override_redirect: bool,
+}
+
+impl ConfigureNotifyEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            event: buf.get_card32(),
+            window: buf.get_card32(),
+            above_sibling: Some(buf.get_card32()).filter(|&window| window != 0),
+            x: buf.get_int16(),
+            y: buf.get_int16(),
+            width: buf.get_card16(),
+            height: buf.get_card16(),
+            border_width: buf.get_card16(),
+            override_redirect: buf.get_u8() != 0,
+        };
+        buf.advance(5); // unused
+
+        this
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PropertyState {
+    NewValue,
+    Deleted,
+}
+
+#[derive(Debug)]
+pub struct PropertyNotifyEvent {
+    pub sequence_number: u16,
+    pub window: WindowId,
+    pub atom: Atom,
+    pub time: u32,
+    pub state: PropertyState,
+}
+
+impl PropertyNotifyEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            window: buf.get_card32(),
+            atom: buf.get_card32(),
+            time: buf.get_card32(),
+            state: if buf.get_u8() == 0 {
+                PropertyState::NewValue
+            } else {
+                PropertyState::Deleted
+            },
+        };
+        buf.advance(15); // unused
+
+        this
+    }
+}
+
+// the owner lost the selection to another owner
+#[derive(Debug)]
+pub struct SelectionClearEvent {
+    pub sequence_number: u16,
+    pub time: u32,
+    pub owner: WindowId,
+    pub selection: Atom,
+}
+
+impl SelectionClearEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            time: buf.get_card32(),
+            owner: buf.get_card32(),
+            selection: buf.get_card32(),
+        };
+        buf.advance(16); // unused
+
+        this
+    }
+}
+
+// Another client asks the owner to convert the selection, sent by
+// ConvertSelection. The owner answers with a SelectionNotify event to
+// the requestor, see `SelectionNotifyEvent`.
+#[derive(Debug)]
+pub struct SelectionRequestEvent {
+    pub sequence_number: u16,
+    // 0 is CurrentTime
+    pub time: u32,
+    pub owner: WindowId,
+    pub requestor: WindowId,
+    pub selection: Atom,
+    pub target: Atom,
+    // `None` by obsolete clients, which expect the target to be used
+    // as the property
+    pub property: Option<Atom>,
+}
+
+impl SelectionRequestEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            time: buf.get_card32(),
+            owner: buf.get_card32(),
+            requestor: buf.get_card32(),
+            selection: buf.get_card32(),
+            target: buf.get_card32(),
+            property: Some(buf.get_card32()).filter(|&property| property != 0),
+        };
+        buf.advance(4); // unused
+
+        this
+    }
+}
+
+// the answer to ConvertSelection
+#[derive(Debug)]
+pub struct SelectionNotifyEvent {
+    pub sequence_number: u16,
+    pub time: u32,
+    pub requestor: WindowId,
+    pub selection: Atom,
+    pub target: Atom,
+    // `None` if the selection couldn't be converted
+    pub property: Option<Atom>,
+}
+
+impl SelectionNotifyEvent {
+    // `buf` starts after the event code
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            time: buf.get_card32(),
+            requestor: buf.get_card32(),
+            selection: buf.get_card32(),
+            target: buf.get_card32(),
+            property: Some(buf.get_card32()).filter(|&property| property != 0),
+        };
+        buf.advance(8); // unused
+
+        this
+    }
+}
+
+// data of a ClientMessage, interpreted according to its format
+#[derive(Debug, PartialEq, Eq)]
+pub enum ClientMessageData {
+    Data8([u8; 20]),
+    Data16([u16; 10]),
+    Data32([u32; 5]),
+}
+
+// a message sent by another client with SendEvent, e.g. WM_PROTOCOLS
+// with WM_DELETE_WINDOW by the window manager
+#[derive(Debug)]
+pub struct ClientMessageEvent {
+    pub sequence_number: u16,
+    pub window: WindowId,
+    pub type_: Atom,
+    pub data: ClientMessageData,
+}
+
+impl ClientMessageEvent {
+    // `buf` starts after the event code, `None` if the format is
+    // invalid
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Option<Self> {
+        let format = buf.get_u8();
+        let sequence_number = buf.get_card16();
+        let window = buf.get_card32();
+        let type_ = buf.get_card32();
+        let data = match format {
+            8 => {
+                let mut data = [0; 20];
+                buf.copy_to_slice(&mut data);
+                ClientMessageData::Data8(data)
+            }
+            16 => ClientMessageData::Data16(std::array::from_fn(|_| buf.get_card16())),
+            32 => ClientMessageData::Data32(std::array::from_fn(|_| buf.get_card32())),
+            _ => {
+                buf.advance(20);
+                return None;
+            }
+        };
+
+        Some(Self {
+            sequence_number,
+            window,
+            type_,
+            data,
+        })
+    }
+}
+
+// the keyboard or pointer mapping changed, e.g. by SetModifierMapping
+#[derive(Debug)]
+pub struct MappingNotifyEvent {
+    pub sequence_number: u16,
+    pub request: MappingNotifyRequest,
+    // the changed range of keycodes for `MappingNotifyRequest::Keyboard`
+    pub first_keycode: u8,
+    pub count: u8,
+}
+
+impl MappingNotifyEvent {
+    // `buf` starts after the event code, `None` if the request is
+    // invalid
+    fn from_bytes(buf: &mut impl OrderedBuf) -> Option<Self> {
+        buf.advance(1); // unused
+        let sequence_number = buf.get_card16();
+        let request = MappingNotifyRequest::from_u8(buf.get_u8());
+        let first_keycode = buf.get_u8();
+        let count = buf.get_u8();
+        buf.advance(25); // unused
+
+        Some(Self {
+            sequence_number,
+            request: request?,
+            first_keycode,
+            count,
+        })
+    }
+}
+
+// An event as forwarded by the reader, see `Connection::next_event`.
+// Events of extensions are decoded by the `ExtensionEventDecoder`
+// registered for them, events which aren't decoded are passed on as
+// the raw 32 bytes.
+#[derive(Debug)]
+pub enum DecodedEvent {
+    KeyPress(InputEvent),
+    KeyRelease(InputEvent),
+    ButtonPress(InputEvent),
+    ButtonRelease(InputEvent),
+    MotionNotify(InputEvent),
+    EnterNotify(CrossingEvent),
+    LeaveNotify(CrossingEvent),
+    Expose(ExposeEvent),
+    GraphicsExposure(GraphicsExposureEvent),
+    NoExposure(NoExposureEvent),
+    ReparentNotify(ReparentNotifyEvent),
+    ConfigureNotify(ConfigureNotifyEvent),
+    PropertyNotify(PropertyNotifyEvent),
+    SelectionClear(SelectionClearEvent),
+    SelectionRequest(SelectionRequestEvent),
+    SelectionNotify(SelectionNotifyEvent),
+    ClientMessage(ClientMessageEvent),
+    MappingNotify(MappingNotifyEvent),
+    Extension(Box<dyn ExtensionEvent>),
+    Other(Bytes),
+}
+
+impl DecodedEvent {
+    // the event of an extension if it is a `T`, like `ShapeNotifyEvent`
+    #[must_use]
+    pub fn extension_event<T: ExtensionEvent>(&self) -> Option<&T> {
+        match self {
+            Self::Extension(event) => (&**event as &dyn Any).downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+// decodes the event `event`, `buf` starts after the event code, `None`
+// if the event isn't decoded yet
+pub(crate) fn decode_event(event: Events, buf: &mut impl OrderedBuf) -> Option<DecodedEvent> {
+    if buf.remaining() < 31 {
+        return None;
+    }
+
+    let decoded = match event {
+        Events::KeyPress => DecodedEvent::KeyPress(InputEvent::from_bytes(buf)),
+        Events::KeyRelease => DecodedEvent::KeyRelease(InputEvent::from_bytes(buf)),
+        Events::ButtonPress => DecodedEvent::ButtonPress(InputEvent::from_bytes(buf)),
+        Events::ButtonRelease => DecodedEvent::ButtonRelease(InputEvent::from_bytes(buf)),
+        Events::MotionNotify => DecodedEvent::MotionNotify(InputEvent::from_bytes(buf)),
+        Events::EnterNotify => DecodedEvent::EnterNotify(CrossingEvent::from_bytes(buf)),
+        Events::LeaveNotify => DecodedEvent::LeaveNotify(CrossingEvent::from_bytes(buf)),
+        Events::Expose => DecodedEvent::Expose(ExposeEvent::from_bytes(buf)),
+        Events::GraphicsExposure => {
+            DecodedEvent::GraphicsExposure(GraphicsExposureEvent::from_bytes(buf))
+        }
+        Events::NoExposure => DecodedEvent::NoExposure(NoExposureEvent::from_bytes(buf)),
+        Events::ReparentNotify => {
+            DecodedEvent::ReparentNotify(ReparentNotifyEvent::from_bytes(buf))
+        }
+        Events::ConfigureNotify => {
+            DecodedEvent::ConfigureNotify(ConfigureNotifyEvent::from_bytes(buf))
+        }
+        Events::PropertyNotify => {
+            DecodedEvent::PropertyNotify(PropertyNotifyEvent::from_bytes(buf))
+        }
+        Events::SelectionClear => {
+            DecodedEvent::SelectionClear(SelectionClearEvent::from_bytes(buf))
+        }
+        Events::SelectionRequest => {
+            DecodedEvent::SelectionRequest(SelectionRequestEvent::from_bytes(buf))
+        }
+        Events::SelectionNotify => {
+            DecodedEvent::SelectionNotify(SelectionNotifyEvent::from_bytes(buf))
+        }
+        Events::ClientMessage => DecodedEvent::ClientMessage(ClientMessageEvent::from_bytes(buf)?),
+        Events::MappingNotify => DecodedEvent::MappingNotify(MappingNotifyEvent::from_bytes(buf)?),
+        _ => return None,
+    };
+
+    Some(decoded)
+}
+
+// an event decoded by an `ExtensionEventDecoder`, forwarded as
+// `DecodedEvent::Extension`
+pub trait ExtensionEvent: Any + fmt::Debug + Send {}
+
+impl<T: Any + fmt::Debug + Send> ExtensionEvent for T {}
+
+// Events of an extension occupy the event codes starting at the
+// first event reported by QueryExtension. Extensions implement this
+// trait to decode their events, which are dispatched by the event
+// code relative to the first event of the extension.
+pub trait ExtensionEventDecoder: Send {
+    fn number_of_events(&self) -> u8;
+
+    // `buf` starts after the event code and holds the remaining 31
+    // bytes of the event, `None` if the event can't be decoded, which
+    // is then forwarded as `DecodedEvent::Other`
+    fn decode_event(&self, event: u8, buf: &mut dyn OrderedBuf) -> Option<Box<dyn ExtensionEvent>>;
+}
@@ -0,0 +1,427 @@
+use super::{
+    big_request_len_words, ensure_remaining, put_rectangle, put_request_length, DecodeError,
+    ExtensionEvent, ExtensionEventDecoder, LengthOverflow, OrderedBuf, OrderedBufMut, PixmapId,
+    Rectangle, WindowId,
+};
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum ShapeKind {
+    Bounding = 0,
+    Clip = 1,
+    Input = 2,
+}
+
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum ShapeOperations {
+    Set = 0,
+    Union = 1,
+    Intersect = 2,
+    Subtract = 3,
+    Invert = 4,
+}
+
+// the order of the rectangles of ShapeRectangles, from none to sorted
+// by y, then x, with bands of rectangles of the same height
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum ShapeOrdering {
+    UnSorted = 0,
+    YSorted = 1,
+    YXSorted = 2,
+    YXBanded = 3,
+}
+
+pub struct ShapeExtension {
+    major_opcode: u8,
+}
+
+#[allow(clippy::unused_self)]
+impl ShapeExtension {
+    #[must_use]
+    pub fn new(major_opcode: u8) -> Self {
+        Self { major_opcode }
+    }
+
+    pub(crate) fn query_version(&self, buf: &mut impl OrderedBufMut) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(0); // shape opcode
+        buf.put_card16(1); // request length
+    }
+
+    // Combines `rectangles`, moved by the offset, with the
+    // `destination_kind` shape of `window_id`. The server may answer
+    // with a Match error if the rectangles aren't in `ordering`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn rectangles(
+        &self,
+        buf: &mut impl OrderedBufMut,
+        operation: ShapeOperations,
+        destination_kind: ShapeKind,
+        ordering: ShapeOrdering,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+        rectangles: &[Rectangle],
+    ) -> Result<(), LengthOverflow> {
+        let request_length = big_request_len_words(16, 8 * rectangles.len())?;
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(1); // shape opcode
+        put_request_length(buf, request_length);
+        buf.put_u8(operation as u8); // shape operation
+        buf.put_u8(destination_kind as u8); // destination kind
+        buf.put_u8(ordering as u8); // ordering
+        buf.put_u8(0); // unused
+        buf.put_card32(window_id); // destination window
+        buf.put_int16(x_offset); // x-offset
+        buf.put_int16(y_offset); // y-offset
+        for rectangle in rectangles {
+            put_rectangle(buf, *rectangle);
+        }
+
+        Ok(())
+    }
+
+    // Combines the set pixels of the bitmap `source_bitmap`, moved by
+    // the offset, with the `destination_kind` shape of `window_id`.
+    // `None` with `ShapeOperations::Set` removes the shape.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn mask(
+        &self,
+        buf: &mut impl OrderedBufMut,
+        operation: ShapeOperations,
+        destination_kind: ShapeKind,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+        source_bitmap: Option<PixmapId>,
+    ) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(2); // shape opcode
+        buf.put_card16(5); // request length
+        buf.put_u8(operation as u8); // shape operation
+        buf.put_u8(destination_kind as u8); // destination kind
+        buf.put_bytes(0, 2); // unused
+        buf.put_card32(window_id); // destination window
+        buf.put_int16(x_offset); // x-offset
+        buf.put_int16(y_offset); // y-offset
+        buf.put_card32(source_bitmap.unwrap_or(0)); // source bitmap, 0 is None
+    }
+
+    // combines the `source_kind` shape of `source_window`, moved by
+    // the offset, with the `destination_kind` shape of `window_id`
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn combine(
+        &self,
+        buf: &mut impl OrderedBufMut,
+        operation: ShapeOperations,
+        destination_kind: ShapeKind,
+        source_kind: ShapeKind,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+        source_window: WindowId,
+    ) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(3); // shape opcode
+        buf.put_card16(5); // request length
+        buf.put_u8(operation as u8); // shape operation
+        buf.put_u8(destination_kind as u8); // destination kind
+        buf.put_u8(source_kind as u8); // source kind
+        buf.put_u8(0); // unused
+        buf.put_card32(window_id); // destination window
+        buf.put_int16(x_offset); // x-offset
+        buf.put_int16(y_offset); // y-offset
+        buf.put_card32(source_window); // source window
+    }
+
+    pub(crate) fn offset(
+        &self,
+        buf: &mut impl OrderedBufMut,
+        destination_kind: ShapeKind,
+        window_id: WindowId,
+        x_offset: i16,
+        y_offset: i16,
+    ) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(4); // shape opcode
+        buf.put_card16(4); // request length
+        buf.put_u8(destination_kind as u8); // destination kind
+        buf.put_bytes(0, 3); // unused
+        buf.put_card32(window_id); // destination window
+        buf.put_int16(x_offset); // x-offset
+        buf.put_int16(y_offset); // y-offset
+    }
+
+    pub(crate) fn query_extents(&self, buf: &mut impl OrderedBufMut, window_id: WindowId) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(5); // shape opcode
+        buf.put_card16(2); // request length
+        buf.put_card32(window_id); // destination window
+    }
+
+    // enables or disables ShapeNotify events of `window_id`
+    pub(crate) fn select_input(
+        &self,
+        buf: &mut impl OrderedBufMut,
+        window_id: WindowId,
+        enable: bool,
+    ) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(6); // shape opcode
+        buf.put_card16(3); // request length
+        buf.put_card32(window_id); // destination window
+        buf.put_u8(enable.into()); // enable
+        buf.put_bytes(0, 3); // unused
+    }
+
+    pub(crate) fn input_selected(&self, buf: &mut impl OrderedBufMut, window_id: WindowId) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(7); // shape opcode
+        buf.put_card16(2); // request length
+        buf.put_card32(window_id); // destination window
+    }
+
+    pub(crate) fn get_rectangles(
+        &self,
+        buf: &mut impl OrderedBufMut,
+        window_id: WindowId,
+        kind: ShapeKind,
+    ) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(8); // shape opcode
+        buf.put_card16(3); // request length
+        buf.put_card32(window_id); // window
+        buf.put_u8(kind as u8); // source kind
+        buf.put_bytes(0, 3); // unused
+    }
+}
+
+// the shape of a window changed, selected with `shape_select_input`
+#[derive(Debug)]
+pub struct ShapeNotifyEvent {
+    pub kind: ShapeKind,
+    pub sequence_number: u16,
+    pub window: WindowId,
+    // the extents of the shape
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+    pub server_time: u32,
+    // false if the shape of `kind` was removed, see ShapeMask with
+    // pixmap None
+    pub shaped: bool,
+}
+
+impl ExtensionEventDecoder for ShapeExtension {
+    fn number_of_events(&self) -> u8 {
+        1
+    }
+
+    fn decode_event(&self, event: u8, buf: &mut dyn OrderedBuf) -> Option<Box<dyn ExtensionEvent>> {
+        // ShapeNotify is the only event
+        let kind = match buf.get_u8() {
+            0 => ShapeKind::Bounding,
+            1 => ShapeKind::Clip,
+            2 => ShapeKind::Input,
+            _ => return None,
+        };
+        let this = ShapeNotifyEvent {
+            kind,
+            sequence_number: buf.get_card16(),
+            window: buf.get_card32(),
+            x: buf.get_int16(),
+            y: buf.get_int16(),
+            width: buf.get_card16(),
+            height: buf.get_card16(),
+            server_time: buf.get_card32(),
+            shaped: buf.get_u8() != 0,
+        };
+        buf.advance(11); // unused
+
+        Some(Box::new(this))
+    }
+}
+
+#[derive(Debug)]
+pub struct ShapeQueryVersionReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+}
+
+impl ShapeQueryVersionReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            major_version: buf.get_card16(),
+            minor_version: buf.get_card16(),
+        };
+        buf.advance(20); // unused
+
+        this
+    }
+}
+
+// The extents of the bounding and clip shape of a window. A shape
+// which isn't set is reported with the extents of the window.
+#[derive(Debug)]
+pub struct ShapeQueryExtentsReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub bounding_shaped: bool,
+    pub clip_shaped: bool,
+    pub bounding_extents: Rectangle,
+    pub clip_extents: Rectangle,
+}
+
+impl ShapeQueryExtentsReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let bounding_shaped = buf.get_u8() != 0;
+        let clip_shaped = buf.get_u8() != 0;
+        buf.advance(2); // unused
+        let mut get_rectangle = || Rectangle {
+            x: buf.get_int16(),
+            y: buf.get_int16(),
+            width: buf.get_card16(),
+            height: buf.get_card16(),
+        };
+        let bounding_extents = get_rectangle();
+        let clip_extents = get_rectangle();
+        buf.advance(4); // unused
+
+        Self {
+            sequence_number,
+            reply_length,
+            bounding_shaped,
+            clip_shaped,
+            bounding_extents,
+            clip_extents,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShapeInputSelectedReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub enabled: bool,
+}
+
+impl ShapeInputSelectedReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(1); // reply
+        let this = Self {
+            enabled: buf.get_u8() != 0,
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+        };
+        buf.advance(24); // unused
+
+        this
+    }
+}
+
+#[derive(Debug)]
+pub struct ShapeGetRectanglesReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    // 0 UnSorted, 1 YSorted, 2 YXSorted, 3 YXBanded
+    pub ordering: u8,
+    pub rectangles: Vec<Rectangle>,
+}
+
+impl ShapeGetRectanglesReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "ShapeGetRectangles reply")?;
+        buf.advance(1); // reply
+        let ordering = buf.get_u8();
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let number_of_rectangles = buf.get_card32() as usize;
+        buf.advance(20); // unused
+        ensure_remaining(buf, number_of_rectangles.saturating_mul(8), "rectangles")?;
+        let rectangles = (0..number_of_rectangles)
+            .map(|_rectangle| Rectangle {
+                x: buf.get_int16(),
+                y: buf.get_int16(),
+                width: buf.get_card16(),
+                height: buf.get_card16(),
+            })
+            .collect();
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            ordering,
+            rectangles,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{ByteOrder, Ordered};
+    use bytes::BytesMut;
+
+    #[test]
+    fn rectangles_request_has_its_length_and_no_uninitialized_bytes() {
+        let shape = ShapeExtension::new(129);
+        let mut buf = Ordered::new(BytesMut::new(), ByteOrder::LsbFirst);
+        let rectangle = Rectangle {
+            x: 1,
+            y: 2,
+            width: 3,
+            height: 4,
+        };
+        shape
+            .rectangles(
+                &mut buf,
+                ShapeOperations::Union,
+                ShapeKind::Input,
+                ShapeOrdering::YXBanded,
+                0x0040_0001,
+                -1,
+                5,
+                &[rectangle, rectangle],
+            )
+            .unwrap();
+
+        assert_eq!(
+            &buf[..],
+            [
+                129, 1, 8, 0, 1, 2, 3, 0, 1, 0, 0x40, 0, 0xff, 0xff, 5, 0, //
+                1, 0, 2, 0, 3, 0, 4, 0, 1, 0, 2, 0, 3, 0, 4, 0,
+            ]
+        );
+    }
+
+    #[test]
+    fn mask_request_takes_operation_and_kind() {
+        let shape = ShapeExtension::new(129);
+        let mut buf = Ordered::new(BytesMut::new(), ByteOrder::LsbFirst);
+        shape.mask(
+            &mut buf,
+            ShapeOperations::Subtract,
+            ShapeKind::Bounding,
+            0x0040_0001,
+            2,
+            -2,
+            None,
+        );
+
+        assert_eq!(
+            &buf[..],
+            [129, 2, 5, 0, 3, 0, 0, 0, 1, 0, 0x40, 0, 2, 0, 0xfe, 0xff, 0, 0, 0, 0]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use super::{OrderedBuf, OrderedBufMut};
+
+// XC-MISC extension, negotiated via `Connection::query_extension` with
+// "XC-MISC", hands out further ids once the ids of the resource id
+// mask are used up, see
+// https://www.x.org/releases/current/doc/xcmiscproto/xc-misc.txt
+pub struct XcMiscExtension {
+    major_opcode: u8,
+}
+
+impl XcMiscExtension {
+    #[must_use]
+    pub fn new(major_opcode: u8) -> Self {
+        Self { major_opcode }
+    }
+
+    pub(crate) fn get_xid_range(&self, buf: &mut impl OrderedBufMut) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(1); // xc-misc opcode
+        buf.put_card16(1); // request length
+    }
+}
+
+// a range of `count` unused ids starting at `start_id`, the ids are
+// `start_id` plus multiples of the lowest bit of the resource id mask
+#[derive(Debug)]
+pub struct GetXidRangeReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub start_id: u32,
+    pub count: u32,
+}
+
+impl GetXidRangeReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            start_id: buf.get_card32(),
+            count: buf.get_card32(),
+        };
+        buf.advance(16); // unused
+
+        this
+    }
+}
@@ -0,0 +1,276 @@
+use super::{ensure_remaining, pad, DecodeError, OrderedBuf, OrderedBufMut, VisualId, WindowId};
+use enumflags2::{bitflags, BitFlags};
+
+// X Video Extension, negotiated via `Connection::query_extension`
+// with "XVideo", see
+// https://www.x.org/releases/current/doc/videoproto/xv-protocol-v2.txt
+pub struct XvExtension {
+    major_opcode: u8,
+}
+
+pub type XvPortId = u32;
+pub type XvEncodingId = u32;
+
+#[bitflags]
+#[derive(Copy, Clone, Debug)]
+#[repr(u8)]
+pub enum XvAdaptorType {
+    InputMask = 0x01,
+    OutputMask = 0x02,
+    VideoMask = 0x04,
+    StillMask = 0x08,
+    ImageMask = 0x10,
+}
+
+impl XvExtension {
+    #[must_use]
+    pub fn new(major_opcode: u8) -> Self {
+        Self { major_opcode }
+    }
+
+    // queries the version of the extension, which Xv calls
+    // QueryExtension
+    pub(crate) fn query_extension(&self, buf: &mut impl OrderedBufMut) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(0); // xv opcode
+        buf.put_card16(1); // request length
+    }
+
+    // adaptors of the screen of `window_id`
+    pub(crate) fn query_adaptors(&self, buf: &mut impl OrderedBufMut, window_id: WindowId) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(1); // xv opcode
+        buf.put_card16(2); // request length
+        buf.put_card32(window_id); // window
+    }
+
+    pub(crate) fn query_encodings(&self, buf: &mut impl OrderedBufMut, port: XvPortId) {
+        buf.put_u8(self.major_opcode); // opcode
+        buf.put_u8(2); // xv opcode
+        buf.put_card16(2); // request length
+        buf.put_card32(port); // port
+    }
+}
+
+#[derive(Debug)]
+pub struct XvQueryExtensionReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub version: u16,
+    pub revision: u16,
+}
+
+impl XvQueryExtensionReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Self {
+        buf.advance(2); // reply, unused
+        let this = Self {
+            sequence_number: buf.get_card16(),
+            reply_length: buf.get_card32(),
+            version: buf.get_card16(),
+            revision: buf.get_card16(),
+        };
+        buf.advance(20); // unused
+
+        this
+    }
+}
+
+#[derive(Debug)]
+pub struct XvFormat {
+    pub visual: VisualId,
+    pub depth: u8,
+}
+
+#[derive(Debug)]
+pub struct XvAdaptorInfo {
+    // the ports of the adaptor are `base_id..base_id + num_ports`
+    pub base_id: XvPortId,
+    pub num_ports: u16,
+    pub type_: BitFlags<XvAdaptorType>,
+    pub name: String,
+    pub formats: Vec<XvFormat>,
+}
+
+impl XvAdaptorInfo {
+    #[must_use]
+    pub fn ports(&self) -> std::ops::Range<XvPortId> {
+        self.base_id..self.base_id + u32::from(self.num_ports)
+    }
+}
+
+#[derive(Debug)]
+pub struct XvQueryAdaptorsReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub adaptors: Vec<XvAdaptorInfo>,
+}
+
+impl XvQueryAdaptorsReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "XvQueryAdaptors reply")?;
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let num_adaptors = buf.get_card16();
+        buf.advance(22); // unused
+
+        let mut adaptors = Vec::with_capacity(num_adaptors.into());
+        for _adaptor in 0..num_adaptors {
+            ensure_remaining(buf, 12, "adaptor")?;
+            let base_id = buf.get_card32();
+            let name_size = usize::from(buf.get_card16());
+            let num_ports = buf.get_card16();
+            let num_formats = buf.get_card16();
+            let type_ = BitFlags::from_bits_truncate(buf.get_u8());
+            buf.advance(1); // unused
+            ensure_remaining(buf, name_size + pad(name_size), "adaptor name")?;
+            let name = String::from_utf8_lossy(&buf.copy_to_bytes(name_size)).into_owned();
+            buf.advance(pad(name_size));
+
+            ensure_remaining(buf, usize::from(num_formats) * 8, "formats")?;
+            let mut formats = Vec::with_capacity(num_formats.into());
+            for _format in 0..num_formats {
+                formats.push(XvFormat {
+                    visual: buf.get_card32(),
+                    depth: buf.get_u8(),
+                });
+                buf.advance(3); // unused
+            }
+
+            adaptors.push(XvAdaptorInfo {
+                base_id,
+                num_ports,
+                type_,
+                name,
+                formats,
+            });
+        }
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            adaptors,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct XvEncodingInfo {
+    pub encoding: XvEncodingId,
+    pub name: String,
+    pub width: u16,
+    pub height: u16,
+    // frame rate as numerator and denominator
+    pub rate: (i32, i32),
+}
+
+#[derive(Debug)]
+pub struct XvQueryEncodingsReply {
+    pub sequence_number: u16,
+    pub reply_length: u32,
+    pub encodings: Vec<XvEncodingInfo>,
+}
+
+impl XvQueryEncodingsReply {
+    pub(crate) fn from_bytes(buf: &mut impl OrderedBuf) -> Result<Self, DecodeError> {
+        ensure_remaining(buf, 32, "XvQueryEncodings reply")?;
+        buf.advance(2); // reply, unused
+        let sequence_number = buf.get_card16();
+        let reply_length = buf.get_card32();
+        let num_encodings = buf.get_card16();
+        buf.advance(22); // unused
+
+        let mut encodings = Vec::with_capacity(num_encodings.into());
+        for _encoding in 0..num_encodings {
+            ensure_remaining(buf, 20, "encoding")?;
+            let encoding = buf.get_card32();
+            let name_size = usize::from(buf.get_card16());
+            let width = buf.get_card16();
+            let height = buf.get_card16();
+            buf.advance(2); // unused
+            let rate = (buf.get_int32(), buf.get_int32());
+            ensure_remaining(buf, name_size + pad(name_size), "encoding name")?;
+            let name = String::from_utf8_lossy(&buf.copy_to_bytes(name_size)).into_owned();
+            buf.advance(pad(name_size));
+
+            encodings.push(XvEncodingInfo {
+                encoding,
+                name,
+                width,
+                height,
+                rate,
+            });
+        }
+
+        Ok(Self {
+            sequence_number,
+            reply_length,
+            encodings,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{ByteOrder, Ordered};
+
+    fn query_adaptors_reply() -> Vec<u8> {
+        let mut reply = vec![1, 0, 3, 0, 7, 0, 0, 0, 1, 0];
+        reply.resize(32, 0);
+        reply.extend_from_slice(&[0x50, 0, 0, 0, 7, 0, 4, 0, 1, 0, 0x13, 0]);
+        reply.extend_from_slice(b"Overlay\0");
+        reply.extend_from_slice(&[0x21, 0, 0, 0, 24, 0, 0, 0]);
+        reply
+    }
+
+    #[test]
+    fn query_adaptors_reply_decodes_adaptors() {
+        let reply = query_adaptors_reply();
+        let reply =
+            XvQueryAdaptorsReply::from_bytes(&mut Ordered::new(&reply[..], ByteOrder::LsbFirst))
+                .unwrap();
+
+        let [adaptor] = &reply.adaptors[..] else {
+            panic!("expected one adaptor: {reply:?}");
+        };
+        assert_eq!(adaptor.name, "Overlay");
+        assert_eq!(adaptor.ports(), 0x50..0x54);
+        assert_eq!(adaptor.formats.len(), 1);
+        assert_eq!(adaptor.formats[0].visual, 0x21);
+        assert_eq!(adaptor.formats[0].depth, 24);
+    }
+
+    #[test]
+    fn query_adaptors_reply_fails_on_truncated_data() {
+        let reply = query_adaptors_reply();
+        for len in 0..reply.len() {
+            let mut buf = Ordered::new(&reply[..len], ByteOrder::LsbFirst);
+            assert!(
+                XvQueryAdaptorsReply::from_bytes(&mut buf).is_err(),
+                "decoded {len} bytes"
+            );
+        }
+    }
+
+    #[test]
+    fn query_encodings_reply_fails_on_truncated_data() {
+        let mut reply = vec![1, 0, 3, 0, 6, 0, 0, 0, 1, 0];
+        reply.resize(32, 0);
+        reply.extend_from_slice(&[1, 0, 0, 0, 4, 0, 0x80, 2, 0xe0, 1, 0, 0]);
+        reply.extend_from_slice(&[1, 0, 0, 0, 1, 0, 0, 0]);
+        reply.extend_from_slice(b"ntsc");
+        let mut buf = Ordered::new(&reply[..], ByteOrder::LsbFirst);
+        let decoded = XvQueryEncodingsReply::from_bytes(&mut buf).unwrap();
+        assert_eq!(decoded.encodings[0].name, "ntsc");
+        assert_eq!(decoded.encodings[0].width, 640);
+
+        for len in 0..reply.len() {
+            let mut buf = Ordered::new(&reply[..len], ByteOrder::LsbFirst);
+            assert!(
+                XvQueryEncodingsReply::from_bytes(&mut buf).is_err(),
+                "decoded {len} bytes"
+            );
+        }
+    }
+}